@@ -0,0 +1,38 @@
+use actix_web::HttpRequest;
+use percent_encoding::percent_decode_str;
+use tracing::warn;
+use x509_parser::pem::parse_x509_pem;
+
+// Subject distinguished name forwarded by the TLS terminator (e.g. nginx `$ssl_client_s_dn`)
+const SUBJECT_DN_HEADER: &str = "X-SSL-Client-S-DN";
+// URL-encoded PEM client certificate (e.g. nginx `$ssl_client_escaped_cert`)
+const CLIENT_CERT_HEADER: &str = "X-Client-Cert";
+
+// Subject of the client certificate a TLS-terminating proxy forwarded, if any
+pub(crate) fn client_cert_subject(req: &HttpRequest) -> Option<String> {
+    let header_value = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+    if let Some(subject) = header_value(SUBJECT_DN_HEADER) {
+        return Some(subject.to_string());
+    }
+
+    let pem = percent_decode_str(header_value(CLIENT_CERT_HEADER)?).decode_utf8_lossy();
+    match subject_from_pem(pem.as_bytes()) {
+        Ok(subject) => Some(subject),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse forwarded client certificate");
+            None
+        }
+    }
+}
+
+fn subject_from_pem(pem: &[u8]) -> Result<String, String> {
+    let (_, pem) = parse_x509_pem(pem).map_err(|e| e.to_string())?;
+    let certificate = pem.parse_x509().map_err(|e| e.to_string())?;
+    Ok(certificate.subject().to_string())
+}
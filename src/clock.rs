@@ -0,0 +1,20 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time for capture timestamps and time-based policies (password expiry,
+/// retention). Tests can substitute their own to move time forward without sleeping.
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the Unix epoch.
+    fn now_millis(&self) -> i64;
+}
+
+/// Clock reading the system time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::RequestData;
+
+// A request the caller expects to find in the bucket
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExpectedRequest {
+    pub method: String,
+    // Path below the bucket name, e.g. `/webhook`
+    pub subpath: String,
+    // Expected body; omitted means the body is not compared
+    pub body: Option<String>,
+}
+
+// A captured request that had no counterpart in the expected set
+#[derive(Serialize, Deserialize)]
+pub struct UnexpectedRequest {
+    pub index: usize,
+    pub method: String,
+    pub subpath: String,
+    pub body: String,
+}
+
+// An expected request that was captured with a different body
+#[derive(Serialize, Deserialize)]
+pub struct MismatchedRequest {
+    pub expected_index: usize,
+    pub actual_index: usize,
+    pub method: String,
+    pub subpath: String,
+    pub expected_body: String,
+    pub actual_body: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CompareResponse {
+    pub matches: bool,
+    pub missing: Vec<ExpectedRequest>,
+    pub unexpected: Vec<UnexpectedRequest>,
+    pub mismatched: Vec<MismatchedRequest>,
+}
+
+// Helper function to get the path of a captured request below its bucket name
+fn subpath<'a>(request: &'a RequestData, bucket_name: &str) -> &'a str {
+    request
+        .path
+        .strip_prefix('/')
+        .and_then(|path| path.strip_prefix(bucket_name))
+        .unwrap_or(&request.path)
+}
+
+// Compare captures against the expected sequence, aligning them in order on method and subpath
+pub(crate) fn compare_requests(
+    bucket_name: &str,
+    expected: &[ExpectedRequest],
+    actual: &VecDeque<RequestData>,
+) -> CompareResponse {
+    let same_route = |e: &ExpectedRequest, a: &RequestData| {
+        e.method.eq_ignore_ascii_case(&a.method) && e.subpath == subpath(a, bucket_name)
+    };
+
+    // Longest common subsequence table over (method, subpath)
+    let (n, m) = (expected.len(), actual.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if same_route(&expected[i], &actual[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut missing = Vec::new();
+    let mut unexpected = Vec::new();
+    let mut mismatched = Vec::new();
+    let mut unexpected_request = |index: usize| UnexpectedRequest {
+        index,
+        method: actual[index].method.clone(),
+        subpath: subpath(&actual[index], bucket_name).to_string(),
+        body: actual[index].body.clone(),
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if same_route(&expected[i], &actual[j]) {
+            if let Some(body) = &expected[i].body {
+                if *body != actual[j].body {
+                    mismatched.push(MismatchedRequest {
+                        expected_index: i,
+                        actual_index: j,
+                        method: actual[j].method.clone(),
+                        subpath: expected[i].subpath.clone(),
+                        expected_body: body.clone(),
+                        actual_body: actual[j].body.clone(),
+                    });
+                }
+            }
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            missing.push(expected[i].clone());
+            i += 1;
+        } else {
+            unexpected.push(unexpected_request(j));
+            j += 1;
+        }
+    }
+    missing.extend(expected[i..].iter().cloned());
+    unexpected.extend((j..m).map(&mut unexpected_request));
+
+    CompareResponse {
+        matches: missing.is_empty() && unexpected.is_empty() && mismatched.is_empty(),
+        missing,
+        unexpected,
+        mismatched,
+    }
+}
@@ -0,0 +1,35 @@
+use std::io::Read;
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+// Helper function to read a decoder to the end, failing once more than `max_bytes` come out
+fn read_limited(reader: impl Read, max_bytes: usize) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    reader
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| e.to_string())?;
+    if decoded.len() > max_bytes {
+        return Err(format!("decompressed body exceeds {} bytes", max_bytes));
+    }
+    Ok(decoded)
+}
+
+// Decode a body sent with `Content-Encoding` gzip, deflate or br (None for other encodings,
+// including several stacked ones). The output is capped at `max_bytes` to defuse zip bombs
+pub(crate) fn decompress_body(
+    encoding: &str,
+    body: &[u8],
+    max_bytes: usize,
+) -> Option<Result<Vec<u8>, String>> {
+    let encoding = encoding.trim().to_ascii_lowercase();
+    let decoded = match encoding.as_str() {
+        "gzip" | "x-gzip" => read_limited(GzDecoder::new(body), max_bytes),
+        // `deflate` should be zlib-wrapped, but some clients send a raw deflate stream
+        "deflate" => read_limited(ZlibDecoder::new(body), max_bytes)
+            .or_else(|_| read_limited(DeflateDecoder::new(body), max_bytes)),
+        "br" => read_limited(brotli::Decompressor::new(body, 4096), max_bytes),
+        _ => return None,
+    };
+    Some(decoded)
+}
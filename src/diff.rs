@@ -0,0 +1,131 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::TextDiff;
+
+use crate::RequestData;
+
+// A header or query parameter whose value differs (None = absent from that request)
+#[derive(Serialize, Deserialize)]
+pub struct FieldChange {
+    pub name: String,
+    pub a: Option<String>,
+    pub b: Option<String>,
+}
+
+// A JSON value that differs, addressed by a dotted path (array elements by index, e.g. `items.0`)
+#[derive(Serialize, Deserialize)]
+pub struct JsonChange {
+    pub path: String,
+    pub a: Option<Value>,
+    pub b: Option<Value>,
+}
+
+// Body differences: per-field when both bodies are JSON, otherwise a unified line diff
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum BodyDiff {
+    Json { changes: Vec<JsonChange> },
+    Text { unified: String },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DiffResponse {
+    pub identical: bool,
+    pub headers: Vec<FieldChange>,
+    pub query_params: Vec<FieldChange>,
+    pub body: BodyDiff,
+}
+
+// Helper function to list the keys whose values differ between two maps, sorted by name
+fn diff_maps(a: &HashMap<String, String>, b: &HashMap<String, String>) -> Vec<FieldChange> {
+    let names: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| a.get(*name) != b.get(*name))
+        .map(|name| FieldChange {
+            name: name.clone(),
+            a: a.get(name).cloned(),
+            b: b.get(name).cloned(),
+        })
+        .collect()
+}
+
+// Helper function to collect the differences between two JSON values, descending into
+// objects and arrays
+fn diff_json(path: &str, a: Option<&Value>, b: Option<&Value>, changes: &mut Vec<JsonChange>) {
+    let child_path = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match (a, b) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for key in keys {
+                diff_json(&child_path(key), a.get(key), b.get(key), changes);
+            }
+        }
+        (Some(Value::Array(a)), Some(Value::Array(b))) => {
+            for index in 0..a.len().max(b.len()) {
+                diff_json(
+                    &child_path(&index.to_string()),
+                    a.get(index),
+                    b.get(index),
+                    changes,
+                );
+            }
+        }
+        (a, b) if a != b => changes.push(JsonChange {
+            path: path.to_string(),
+            a: a.cloned(),
+            b: b.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+// Helper function to diff two bodies, as JSON values when both parse as JSON
+fn diff_bodies(a: &str, b: &str) -> BodyDiff {
+    let parsed = (
+        serde_json::from_str::<Value>(a),
+        serde_json::from_str::<Value>(b),
+    );
+    if let (Ok(a), Ok(b)) = parsed {
+        let mut changes = Vec::new();
+        diff_json("", Some(&a), Some(&b), &mut changes);
+        return BodyDiff::Json { changes };
+    }
+
+    let unified = if a == b {
+        String::new()
+    } else {
+        TextDiff::from_lines(a, b)
+            .unified_diff()
+            .header("a", "b")
+            .to_string()
+    };
+    BodyDiff::Text { unified }
+}
+
+// Compare two captured requests
+pub(crate) fn diff_requests(a: &RequestData, b: &RequestData) -> DiffResponse {
+    let headers = diff_maps(&a.headers, &b.headers);
+    let query_params = diff_maps(&a.query_params, &b.query_params);
+    let body = diff_bodies(&a.body, &b.body);
+    let body_identical = match &body {
+        BodyDiff::Json { changes } => changes.is_empty(),
+        BodyDiff::Text { unified } => unified.is_empty(),
+    };
+
+    DiffResponse {
+        identical: headers.is_empty() && query_params.is_empty() && body_identical,
+        headers,
+        query_params,
+        body,
+    }
+}
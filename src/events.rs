@@ -0,0 +1,88 @@
+use crate::RequestData;
+
+/// Destination for capture events, notified after each request is stored.
+///
+/// Implementations must not block: `capture_request` calls `publish` before responding,
+/// so any I/O should be handed off to a background task.
+pub trait EventSink: Send + Sync {
+    fn publish(&self, bucket_name: &str, request: &RequestData);
+}
+
+/// Sink used when no event integration is configured.
+pub struct NoopEventSink;
+
+impl EventSink for NoopEventSink {
+    fn publish(&self, _bucket_name: &str, _request: &RequestData) {}
+}
+
+// Build the event sink from environment variables (`KAFKA_BROKERS` / `KAFKA_TOPIC`)
+#[cfg(feature = "kafka")]
+pub(crate) fn sink_from_env() -> Result<Box<dyn EventSink>, String> {
+    let brokers = crate::parse_env_var::<String>("KAFKA_BROKERS")?;
+    let topic = crate::parse_env_var::<String>("KAFKA_TOPIC")?;
+    match (brokers, topic) {
+        (Some(brokers), Some(topic)) => Ok(Box::new(kafka::KafkaEventSink::new(&brokers, topic)?)),
+        (None, None) => Ok(Box::new(NoopEventSink)),
+        _ => Err("KAFKA_BROKERS and KAFKA_TOPIC must be set together".into()),
+    }
+}
+
+#[cfg(not(feature = "kafka"))]
+pub(crate) fn sink_from_env() -> Result<Box<dyn EventSink>, String> {
+    if std::env::var_os("KAFKA_BROKERS").is_some() {
+        tracing::warn!("KAFKA_BROKERS is set but the kafka feature is not enabled; ignoring");
+    }
+    Ok(Box::new(NoopEventSink))
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use std::time::Duration;
+
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use rdkafka::ClientConfig;
+    use tracing::warn;
+
+    use super::EventSink;
+    use crate::RequestData;
+
+    // How long a publish may wait for space in the producer queue
+    const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub(super) struct KafkaEventSink {
+        producer: FutureProducer,
+        topic: String,
+    }
+
+    impl KafkaEventSink {
+        pub(super) fn new(brokers: &str, topic: String) -> Result<Self, String> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                .create()
+                .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
+            Ok(KafkaEventSink { producer, topic })
+        }
+    }
+
+    impl EventSink for KafkaEventSink {
+        fn publish(&self, bucket_name: &str, request: &RequestData) {
+            let payload = match serde_json::to_string(request) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!(error = %e, "Failed to serialize capture event");
+                    return;
+                }
+            };
+            let producer = self.producer.clone();
+            let topic = self.topic.clone();
+            let key = bucket_name.to_string();
+
+            tokio::spawn(async move {
+                let record = FutureRecord::to(&topic).key(&key).payload(&payload);
+                if let Err((e, _)) = producer.send(record, QUEUE_TIMEOUT).await {
+                    warn!(error = %e, bucket_name = %key, "Failed to publish capture event to Kafka");
+                }
+            });
+        }
+    }
+}
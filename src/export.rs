@@ -0,0 +1,70 @@
+use crate::RequestData;
+
+// Headers curl derives itself, so replaying them would only conflict
+const SKIPPED_HEADERS: &[&str] = &["host", "content-length"];
+
+// Render a captured request as a runnable curl command against `base_url`, leaving out the
+// bucket password sent in `password_header`
+pub(crate) fn curl_command(request: &RequestData, base_url: &str, password_header: &str) -> String {
+    let mut url = format!("{}{}", base_url, request.path);
+    // The query string is replayed as sent; `query_params` are never decoded, so they're joined
+    // as they are for requests stored before `raw_query` was recorded
+    let query = if request.raw_query.is_empty() {
+        let mut params: Vec<_> = request.query_params.iter().collect();
+        params.sort();
+        params
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join("&")
+    } else {
+        request.raw_query.clone()
+    };
+    if !query.is_empty() {
+        url = format!("{}?{}", url, query);
+    }
+
+    let mut command = format!(
+        "curl -X {} {}",
+        shell_quote(&request.method),
+        shell_quote(&url)
+    );
+
+    let mut headers: Vec<_> = request
+        .headers
+        .iter()
+        .filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.as_str()))
+        .filter(|(name, _)| !name.eq_ignore_ascii_case(password_header))
+        // Bodies are stored decompressed, so the original Content-Encoding would mislabel them
+        .filter(|(name, _)| {
+            !(request.decompressed && name.eq_ignore_ascii_case("content-encoding"))
+        })
+        .collect();
+    headers.sort();
+    for (name, value) in headers {
+        command.push_str(" -H ");
+        command.push_str(&shell_quote(&format!("{}: {}", name, value)));
+    }
+
+    // Binary gRPC-Web bodies are decoded from base64 and piped in byte for byte
+    if let Some(encoded) = &request.body_base64 {
+        return format!(
+            "printf %s {} | base64 -d | {} --data-binary @-",
+            shell_quote(encoded),
+            command
+        );
+    }
+
+    // --data-raw is --data without curl's special handling of a leading `@`
+    if !request.body.is_empty() {
+        command.push_str(" --data-raw ");
+        command.push_str(&shell_quote(&request.body));
+    }
+
+    command
+}
+
+// Quote a value for POSIX shells: wrap in single quotes and escape embedded single quotes
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
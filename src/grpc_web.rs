@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+// Flag bit marking a gRPC-Web frame as the trailers rather than a message
+const TRAILER_FLAG: u8 = 0x80;
+// Flag byte plus the 4-byte big-endian frame length
+const FRAME_HEADER_LEN: usize = 5;
+
+// Helper function to check for a binary gRPC-Web content type (`application/grpc-web` or
+// `application/grpc-web+proto` and the like; the base64 `-text` variant is not framed as bytes)
+pub(crate) fn is_grpc_web(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime == "application/grpc-web" || mime.starts_with("application/grpc-web+")
+}
+
+// Walk the length-prefixed frames of a gRPC-Web body and parse the trailer frame's
+// `name: value` lines (e.g. `grpc-status`, `grpc-message`), with lowercased names. Best effort:
+// None when there is no trailer frame or the framing is cut short.
+pub(crate) fn parse_trailers(body: &[u8]) -> Option<HashMap<String, String>> {
+    let mut rest = body;
+    while rest.len() >= FRAME_HEADER_LEN {
+        let flags = rest[0];
+        let length = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        let end = FRAME_HEADER_LEN.checked_add(length)?;
+        let data = rest.get(FRAME_HEADER_LEN..end)?;
+        if flags & TRAILER_FLAG != 0 {
+            let trailers = String::from_utf8_lossy(data)
+                .split("\r\n")
+                .filter_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    Some((name.trim().to_ascii_lowercase(), value.trim().to_string()))
+                })
+                .collect();
+            return Some(trailers);
+        }
+        rest = &rest[end..];
+    }
+    None
+}
@@ -1,358 +1,4637 @@
-use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web::dev::{AppService, HttpServiceFactory};
+use actix_web::error::{InternalError, JsonPayloadError, PayloadError};
+use actix_web::http::{header, Method, StatusCode, Uri};
+use actix_web::{
+    web, FromRequest, Handler, HttpRequest, HttpResponse, HttpResponseBuilder, Resource, Responder,
+};
+use base64::Engine;
+use dashmap::mapref::one::{Ref, RefMut};
 use dashmap::DashMap;
+use futures_util::{stream, StreamExt};
+use hmac::{Hmac, Mac};
+use percent_encoding::percent_decode_str;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json_path::JsonPath;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{vec_deque, BTreeMap, HashMap, HashSet, VecDeque};
+use std::env;
+use std::iter;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use subtle::ConstantTimeEq;
+use tokio::sync::{broadcast, Notify};
 use tracing::{error, info, instrument, warn};
 
+mod client_cert;
+mod clock;
+mod compare;
+mod decompress;
+mod diff;
+mod events;
+mod export;
+mod grpc_web;
+mod multipart;
+mod persist;
+mod store;
+mod transform;
+
+pub use clock::{Clock, SystemClock};
+pub use compare::{CompareResponse, ExpectedRequest, MismatchedRequest, UnexpectedRequest};
+pub use diff::{BodyDiff, DiffResponse, FieldChange, JsonChange};
+pub use events::{EventSink, NoopEventSink};
+pub use multipart::MultipartPart;
+pub use persist::{restore_snapshot, write_snapshot, PersistConfig};
+#[cfg(feature = "redis")]
+pub use store::redis_store;
+pub use store::{BucketCounter, SharedStore, StoreFuture};
+pub use transform::JqFilter;
+
 // Constants
-const PASSWORD_HEADER: &str = "X-Bucket-Password";
+/// Maximum request body size, also applied to capture bodies after decompression.
+pub const MAX_PAYLOAD_SIZE: usize = 10 * 1024 * 1024; // 10MB
+const DEFAULT_PASSWORD_HEADER: &str = "x-bucket-password";
+// Compared against the given password for missing buckets when HIDE_BUCKET_EXISTENCE is set
+const MISSING_BUCKET_PASSWORD: &str = "missing-bucket-placeholder";
+const ADMIN_TOKEN_HEADER: &str = "X-Admin-Token";
 const MAX_REQUESTS_PER_BUCKET: usize = 1000;
 const DEFAULT_PAGE_SIZE: usize = 50;
 const MAX_PAGE_SIZE: usize = 500;
+const MAX_RESPONSE_DELAY_MS: u64 = 30_000;
+const DEFAULT_MULTIPART_INLINE_BYTES: usize = 64 * 1024;
+const REGEX_CACHE_TTL: Duration = Duration::from_secs(60);
+const REGEX_CACHE_MAX_ENTRIES: usize = 256;
+const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+
+// Subscriptions that haven't polled for this long are dropped
+const SUBSCRIPTION_TTL: Duration = Duration::from_secs(10 * 60);
+
+// How long a create request's Idempotency-Key is remembered
+const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(10 * 60);
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+// Request headers overriding the capture response when `allow_mock_response` is set
+const MOCK_STATUS_HEADER: &str = "X-Mock-Status";
+const MOCK_BODY_HEADER: &str = "X-Mock-Body";
+
+// Default caps on captured headers (generous enough for normal traffic)
+const DEFAULT_MAX_CAPTURED_HEADERS: usize = 200;
+const DEFAULT_MAX_HEADER_VALUE_BYTES: usize = 8 * 1024;
+const DEFAULT_MAX_CAPTURED_PATH_BYTES: usize = 8 * 1024;
+
+// Longest a long-poll request may wait for new captures
+const MAX_LONG_POLL_MS: u64 = 30_000;
+
+// Most matches returned by the cross-bucket admin search
+const MAX_SEARCH_RESULTS: usize = 100;
+
+// Most buckets a single batch fetch may ask for, and the requests returned per bucket by default
+const MAX_BATCH_BUCKETS: usize = 50;
+const DEFAULT_BATCH_LIMIT: usize = 10;
+
+// Captures buffered per admin stream subscriber; slower subscribers miss the oldest ones
+const CAPTURE_STREAM_CAPACITY: usize = 256;
+
+// How often long-polls re-check the shared store for captures made on other instances
+const SHARED_STORE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Upstream timeout for replayed requests
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Longest a bucket's jq program may run on one body before the capture is stored untransformed
+const DEFAULT_JQ_FILTER_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Headers that are never forwarded when replaying a captured request
+// (besides the configured password header)
+const REPLAY_SKIPPED_HEADERS: &[&str] = &[
+    "host",
+    "content-length",
+    // Hop-by-hop headers (RFC 7230 section 6.1)
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+// OpenAPI description of the management API (`__VERSION__` is replaced with the crate version)
+const OPENAPI_SPEC: &str = include_str!("openapi.json");
+
+// Template for the embeddable read-only bucket feed
+const EMBED_TEMPLATE: &str = include_str!("embed.html");
+
+// Bucket names that are always reserved (conflicts with routes)
+const DEFAULT_RESERVED_BUCKET_NAMES: &[&str] = &["api", "ui"];
 
-// Reserved bucket names that cannot be used (conflicts with routes)
-const RESERVED_BUCKET_NAMES: &[&str] = &["api", "ui"];
+// Default maximum bucket name length, overridable with BUCKET_NAME_MAX_LEN
+const DEFAULT_BUCKET_NAME_MAX_LEN: usize = 100;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct RequestData {
     pub path: String,
     pub method: String,
     pub query_params: HashMap<String, String>,
+    // Query string exactly as sent (order and encoding preserved), without the `?`
+    #[serde(default)]
+    pub raw_query: String,
     pub headers: HashMap<String, String>,
     pub body: String,
+    // Body as received when the bucket's `jq_filter` transformed it into `body`
+    #[serde(default)]
+    pub raw_body: Option<String>,
+    // Base64 of the raw body bytes for binary gRPC-Web requests, which `body` can't hold losslessly
+    #[serde(default)]
+    pub body_base64: Option<String>,
+    // Trailers (`grpc-status`, `grpc-message`, ...) from a gRPC-Web body's trailer frame
+    #[serde(default)]
+    pub grpc_trailers: Option<HashMap<String, String>>,
     pub timestamp: i64,
+    pub http_version: String,
+    pub segments: Vec<String>,
+    // Parsed parts for multipart/form-data requests
+    pub parts: Option<Vec<MultipartPart>>,
+    // Total size of all multipart part contents
+    pub upload_bytes: Option<usize>,
+    // Whether the capture was answered with a simulated failure (see `fail_first_n`)
+    #[serde(default)]
+    pub failed: bool,
+    // Hex SHA-256 of the raw body bytes as received
+    #[serde(default)]
+    pub body_sha256: String,
+    // Size of `body` in bytes, kept even when the bucket doesn't store bodies (`store_body`)
+    #[serde(default)]
+    pub body_length: usize,
+    // Whether headers were dropped or values shortened to fit the capture limits
+    #[serde(default)]
+    pub headers_truncated: bool,
+    // Labels attached after capture to mark requests for triage
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Host the sender addressed (Host header, or the HTTP/2 authority)
+    #[serde(default)]
+    pub host: Option<String>,
+    // Scheme the request arrived with (honours Forwarded / X-Forwarded-Proto)
+    #[serde(default)]
+    pub scheme: String,
+    // `scheme://host/path?query` as sent, when the host is known
+    #[serde(default)]
+    pub full_url: Option<String>,
+    // Content-Encoding header of the request, as sent
+    #[serde(default)]
+    pub content_encoding: Option<String>,
+    // Whether `body` was decompressed according to `content_encoding`
+    #[serde(default)]
+    pub decompressed: bool,
+    // Whether `path` was cut to fit MAX_CAPTURED_PATH_BYTES
+    #[serde(default)]
+    pub path_truncated: bool,
+    // First path segment after the bucket name, to tell traffic to sub-paths apart
+    #[serde(default)]
+    pub channel: Option<String>,
+    // Position in the bucket's capture order (one more per capture, never reused after a clear)
+    #[serde(default)]
+    pub seq: u64,
+    // Time spent in `capture_request` before the capture was stored (reading, decoding, checks)
+    #[serde(default)]
+    pub processing_micros: u64,
+    // Client certificate subject forwarded by a TLS-terminating proxy (with CAPTURE_CLIENT_CERT)
+    #[serde(default)]
+    pub client_cert_subject: Option<String>,
+    // How the request reached the server
+    #[serde(default)]
+    pub connection_info: Option<ConnectionInfo>,
+}
+
+impl RequestData {
+    // Bytes of body content held for this request, counted against the storage limits
+    fn stored_size(&self) -> usize {
+        self.body.len()
+            + self.raw_body.as_ref().map_or(0, String::len)
+            + self.body_base64.as_ref().map_or(0, String::len)
+    }
+
+    // Recompute the fields the server derives at capture time for a request supplied by a client
+    // (validated with `validate_request_data`), so they can't be made up
+    fn reset_derived_fields(&mut self) {
+        let received = match &self.body_base64 {
+            Some(encoded) => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .unwrap_or_default(),
+            None => self
+                .raw_body
+                .as_ref()
+                .unwrap_or(&self.body)
+                .as_bytes()
+                .to_vec(),
+        };
+        self.body_length = received.len();
+        self.body_sha256 = hex::encode(Sha256::digest(&received));
+        self.failed = false;
+        self.processing_micros = 0;
+        self.seq = 0;
+    }
+}
+
+// Protocol details of the connection a request arrived on
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConnectionInfo {
+    pub http_version: String,
+    // Inferred from the scheme, so requests forwarded by a TLS-terminating proxy count as TLS
+    pub is_tls: bool,
+}
+
+// Per-bucket behaviour options, set at creation time
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BucketConfig {
+    // Fixed delay applied to capture responses (simulates a slow upstream)
+    pub response_delay_ms: Option<u64>,
+    // Random delay range applied to capture responses, as an alternative to a fixed delay
+    pub response_delay_min_ms: Option<u64>,
+    pub response_delay_max_ms: Option<u64>,
+    // Multipart parts up to this size keep their content inline (default 64KB)
+    pub multipart_inline_max_bytes: Option<usize>,
+    // Reject (413) and don't store requests whose body is larger than this
+    #[serde(alias = "max_body_bytes")]
+    pub reject_body_over_bytes: Option<usize>,
+    // Answer the first N captures with 503 (they are still stored) to exercise client retries
+    pub fail_first_n: Option<usize>,
+    // Only accept captures whose Origin (or Referer) matches this origin, e.g. `https://example.com`
+    pub require_origin: Option<String>,
+    // Store valid JSON bodies re-serialized without insignificant whitespace
+    pub minify_json: Option<bool>,
+    // Only capture these HTTP methods (others get 405); None captures everything
+    pub allowed_methods: Option<Vec<String>>,
+    // Evict the oldest requests to keep the total stored body size under this many bytes
+    pub max_total_bytes: Option<usize>,
+    // Only store captures with one of these content types (others are answered but dropped)
+    pub capture_content_types: Option<Vec<String>>,
+    // Store each capture with this probability, in (0, 1]; the others are answered but dropped
+    pub sample_rate: Option<f64>,
+    // Let senders pick the capture response with `X-Mock-Status` / `X-Mock-Body` headers
+    pub allow_mock_response: Option<bool>,
+    // What happens to captures once the bucket holds MAX_REQUESTS_PER_BUCKET requests
+    pub overflow_policy: Option<OverflowPolicy>,
+    // Keep request bodies (default true); when false only `body_length` and `body_sha256` are
+    // recorded and the body is hashed as it streams in instead of being buffered
+    pub store_body: Option<bool>,
+    // jq program applied to JSON bodies before they are stored, e.g. `.data.event`
+    pub jq_filter: Option<JqFilter>,
+    // Drop requests captured more than this many seconds ago
+    pub retention_seconds: Option<u64>,
+    // Stub mode: answer captures with the body of the stored request at this index
+    pub replay_from_index: Option<usize>,
+    // Answer but don't store captures whose User-Agent contains one of these (case-insensitive)
+    pub ignore_user_agents: Option<Vec<String>>,
+    // Answer captures sent with `Accept: application/json` with the captured request as JSON
+    pub echo: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    // Drop the oldest request to make room
+    #[default]
+    EvictOldest,
+    // Refuse the capture with 507 and keep the stored requests
+    Reject,
+}
+
+impl BucketConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.response_delay_ms.is_some()
+            && (self.response_delay_min_ms.is_some() || self.response_delay_max_ms.is_some())
+        {
+            return Err(
+                "response_delay_ms cannot be combined with response_delay_min_ms/response_delay_max_ms"
+                    .into(),
+            );
+        }
+        let (min, max) = self.response_delay_range();
+        if min > max {
+            return Err(
+                "response_delay_min_ms cannot be greater than response_delay_max_ms".into(),
+            );
+        }
+        if max > MAX_RESPONSE_DELAY_MS {
+            return Err(format!(
+                "Response delay cannot exceed {} milliseconds",
+                MAX_RESPONSE_DELAY_MS
+            ));
+        }
+        if let Some(methods) = &self.allowed_methods {
+            if methods.is_empty() {
+                return Err("allowed_methods cannot be empty".into());
+            }
+            if let Some(invalid) = methods
+                .iter()
+                .find(|m| Method::from_bytes(m.trim().as_bytes()).is_err())
+            {
+                return Err(format!(
+                    "Invalid HTTP method in allowed_methods: {}",
+                    invalid
+                ));
+            }
+        }
+        if self
+            .capture_content_types
+            .as_ref()
+            .is_some_and(|types| types.is_empty())
+        {
+            return Err("capture_content_types cannot be empty".into());
+        }
+        if let Some(rate) = self.sample_rate {
+            if !(rate > 0.0 && rate <= 1.0) {
+                return Err("sample_rate must be greater than 0 and at most 1".into());
+            }
+        }
+        if self
+            .ignore_user_agents
+            .iter()
+            .flatten()
+            .any(|agent| agent.trim().is_empty())
+        {
+            return Err("ignore_user_agents cannot contain empty entries".into());
+        }
+        if self.retention_seconds == Some(0) {
+            return Err("retention_seconds must be greater than 0".into());
+        }
+        Ok(())
+    }
+
+    // Resolve the configured delay bounds: a fixed delay wins, otherwise a missing bound
+    // is treated as equal to the other one
+    fn response_delay_range(&self) -> (u64, u64) {
+        if let Some(delay) = self.response_delay_ms {
+            return (delay, delay);
+        }
+        match (self.response_delay_min_ms, self.response_delay_max_ms) {
+            (Some(min), Some(max)) => (min, max),
+            (Some(min), None) => (min, min),
+            (None, Some(max)) => (0, max),
+            (None, None) => (0, 0),
+        }
+    }
+
+    // Whether captures with this method should be stored (method names compare case-insensitively)
+    fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods.as_ref().is_none_or(|methods| {
+            methods
+                .iter()
+                .any(|allowed| allowed.trim().eq_ignore_ascii_case(method))
+        })
+    }
+
+    // Whether captures with this content type should be stored (parameters and case are ignored)
+    fn captures_content_type(&self, content_type: Option<&str>) -> bool {
+        let Some(types) = &self.capture_content_types else {
+            return true;
+        };
+        let mime = content_type
+            .and_then(|value| value.split(';').next())
+            .unwrap_or_default()
+            .trim();
+        types
+            .iter()
+            .any(|allowed| allowed.trim().eq_ignore_ascii_case(mime))
+    }
+
+    // Whether captures from this User-Agent are answered without being stored
+    fn ignores_user_agent(&self, user_agent: Option<&str>) -> bool {
+        let Some(user_agent) = user_agent.map(str::to_lowercase) else {
+            return false;
+        };
+        self.ignore_user_agents
+            .iter()
+            .flatten()
+            .any(|ignored| user_agent.contains(&ignored.trim().to_lowercase()))
+    }
+
+    // Pick the delay to apply before responding to a capture, if any
+    fn response_delay(&self) -> Option<Duration> {
+        let (min, max) = self.response_delay_range();
+        if max == 0 || min > max {
+            return None;
+        }
+        Some(Duration::from_millis(rand::rng().random_range(min..=max)))
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Bucket {
     pub password: String,
+    pub requests: VecDeque<RequestData>,
+    // Milliseconds since the Unix epoch after which the password must be rotated
+    #[serde(default)]
+    pub password_expires_at: Option<i64>,
+    #[serde(default)]
+    pub config: BucketConfig,
+    // Id of the most recently captured request (ids increase by one per capture)
+    #[serde(default)]
+    pub last_id: u64,
+    // Incremented every time the bucket is cleared so stale watermarks can be detected
+    #[serde(default)]
+    pub generation: u64,
+    // Running total of stored body bytes, checked against `max_total_bytes`
+    #[serde(default)]
+    pub stored_bytes: usize,
+    // Incremented whenever stored requests are modified in place (e.g. tagged)
+    #[serde(default)]
+    pub revision: u64,
+    // Captures answered but not stored because of `capture_content_types`
+    #[serde(default)]
+    pub dropped_count: u64,
+    // Captures answered but not stored because of `sample_rate`
+    #[serde(default)]
+    pub sampled_out_count: u64,
+    // Captures answered but not stored because of `ignore_user_agents`
+    #[serde(default)]
+    pub ignored_count: u64,
+    // Captures stored since the bucket was last cleared, so `fail_first_n` starts over after a
+    // clear (`last_id` keeps counting for watermarks)
+    #[serde(default)]
+    pub capture_count: u64,
+    // Cleared while capture is paused (captures are answered but not stored)
+    #[serde(default = "capture_enabled_default")]
+    pub enabled: bool,
+}
+
+fn capture_enabled_default() -> bool {
+    true
+}
+
+impl Bucket {
+    pub fn new(password: String, config: BucketConfig) -> Self {
+        Bucket {
+            password,
+            requests: VecDeque::new(),
+            password_expires_at: None,
+            config,
+            last_id: 0,
+            generation: 0,
+            stored_bytes: 0,
+            revision: 0,
+            dropped_count: 0,
+            sampled_out_count: 0,
+            ignored_count: 0,
+            capture_count: 0,
+            enabled: true,
+        }
+    }
+
+    // Store a request, evicting the oldest ones to stay within the count and byte limits
+    fn push_request(&mut self, mut request: RequestData) {
+        self.last_id += 1;
+        request.seq = self.last_id;
+        self.push_loaded(request);
+    }
+
+    // Store a request that already has its `seq` (e.g. read back from the shared store), evicting
+    // like `push_request`
+    fn push_loaded(&mut self, request: RequestData) {
+        let size = request.stored_size();
+        let max_total_bytes = self.config.max_total_bytes.unwrap_or(usize::MAX);
+        while !self.requests.is_empty()
+            && (self.requests.len() >= MAX_REQUESTS_PER_BUCKET
+                || self.stored_bytes.saturating_add(size) > max_total_bytes)
+        {
+            self.evict_oldest();
+        }
+
+        self.stored_bytes += size;
+        self.requests.push_back(request);
+    }
+
+    // Remove every stored request and reset the counters that describe them, invalidating
+    // outstanding watermarks. `last_id` keeps counting so `seq` values are never reused.
+    // Returns the bytes freed
+    fn clear(&mut self) -> usize {
+        self.requests.clear();
+        self.generation += 1;
+        self.dropped_count = 0;
+        self.sampled_out_count = 0;
+        self.ignored_count = 0;
+        self.capture_count = 0;
+        std::mem::take(&mut self.stored_bytes)
+    }
+
+    pub fn counter(&self, counter: BucketCounter) -> u64 {
+        match counter {
+            BucketCounter::Dropped => self.dropped_count,
+            BucketCounter::SampledOut => self.sampled_out_count,
+            BucketCounter::Ignored => self.ignored_count,
+            BucketCounter::Revision => self.revision,
+            BucketCounter::Captured => self.capture_count,
+        }
+    }
+
+    pub fn counter_mut(&mut self, counter: BucketCounter) -> &mut u64 {
+        match counter {
+            BucketCounter::Dropped => &mut self.dropped_count,
+            BucketCounter::SampledOut => &mut self.sampled_out_count,
+            BucketCounter::Ignored => &mut self.ignored_count,
+            BucketCounter::Revision => &mut self.revision,
+            BucketCounter::Captured => &mut self.capture_count,
+        }
+    }
+
+    // Drop the oldest stored request, returning the bytes freed
+    fn evict_oldest(&mut self) -> usize {
+        let size = self.requests.pop_front().map_or(0, |r| r.stored_size());
+        self.stored_bytes -= size;
+        size
+    }
+
+    // Drop the requests older than `retention_seconds` as of `now` (ms), returning their `seq`s
+    fn evict_expired(&mut self, now: i64) -> Vec<u64> {
+        let Some(retention) = self.config.retention_seconds else {
+            return Vec::new();
+        };
+        let cutoff = now.saturating_sub(retention.saturating_mul(1000) as i64);
+        // Seeded and imported requests may be out of timestamp order, so check every request
+        let mut expired = Vec::new();
+        let mut freed = 0;
+        self.requests.retain(|request| {
+            let keep = request.timestamp >= cutoff;
+            if !keep {
+                expired.push(request.seq);
+                freed += request.stored_size();
+            }
+            keep
+        });
+        self.stored_bytes -= freed;
+        expired
+    }
+
+    pub fn watermark(&self) -> Watermark {
+        Watermark {
+            generation: self.generation,
+            last_id: self.last_id,
+        }
+    }
+
+    // Requests whose `seq` is after the given id, or None if some of them are no longer stored
+    fn requests_since(&self, last_id: u64) -> Option<vec_deque::Iter<'_, RequestData>> {
+        if last_id > self.last_id {
+            return None;
+        }
+        // Found by `seq`, as retention can remove requests from the middle
+        let start = self
+            .requests
+            .partition_point(|request| request.seq <= last_id);
+        let since = self.requests.range(start..);
+        // `seq`s are consecutive, so a request missing after the id leaves the range short
+        if since.len() as u64 != self.last_id - last_id {
+            return None;
+        }
+        Some(since)
+    }
+}
+
+// Opaque cursor handed to clients for incremental polling
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Watermark {
+    pub generation: u64,
+    pub last_id: u64,
+}
+
+impl Watermark {
+    pub fn encode(&self) -> String {
+        format!("{:x}.{:x}", self.generation, self.last_id)
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let (generation, last_id) = value.split_once('.')?;
+        Some(Watermark {
+            generation: u64::from_str_radix(generation, 16).ok()?,
+            last_id: u64::from_str_radix(last_id, 16).ok()?,
+        })
+    }
+}
+
+// Portable representation of a bucket used for dump/import (never includes the password)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BucketExport {
     pub requests: Vec<RequestData>,
+    #[serde(default)]
+    pub config: BucketConfig,
+}
+
+// Short-lived cache of compiled filter regexes so hot polling loops don't recompile
+#[derive(Default)]
+pub struct RegexCache {
+    entries: DashMap<String, (Regex, Instant)>,
+}
+
+impl RegexCache {
+    fn get_or_compile(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        if let Some(entry) = self.entries.get(pattern) {
+            if entry.1.elapsed() < REGEX_CACHE_TTL {
+                return Ok(entry.0.clone());
+            }
+        }
+
+        let regex = RegexBuilder::new(pattern)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .build()?;
+
+        if self.entries.len() >= REGEX_CACHE_MAX_ENTRIES {
+            self.entries
+                .retain(|_, (_, compiled_at)| compiled_at.elapsed() < REGEX_CACHE_TTL);
+            if self.entries.len() >= REGEX_CACHE_MAX_ENTRIES {
+                self.entries.clear();
+            }
+        }
+        self.entries
+            .insert(pattern.to_string(), (regex.clone(), Instant::now()));
+        Ok(regex)
+    }
+}
+
+// Server-managed polling cursor for one subscriber of a bucket
+pub struct Subscription {
+    pub bucket_name: String,
+    pub cursor: Watermark,
+    pub last_polled: Instant,
+}
+
+// Bucket created by a request carrying an Idempotency-Key
+pub struct IdempotentCreate {
+    pub bucket_name: String,
+    pub created_at: Instant,
 }
 
 pub struct AppState {
     pub buckets: DashMap<String, Bucket>,
+    // Maximum number of buckets that can exist at once (None = unlimited)
+    pub max_buckets: Option<usize>,
+    // Bucket creations in progress, counted against `max_buckets` until their bucket is inserted
+    pub creating_buckets: AtomicUsize,
+    // Create buckets on first capture instead of returning 404 (they get an empty password)
+    pub auto_create_buckets: bool,
+    pub regex_cache: RegexCache,
+    // Receives every captured request (e.g. to publish it to Kafka)
+    pub event_sink: Box<dyn EventSink>,
+    // Status and body returned for successful captures
+    pub capture_response_status: StatusCode,
+    pub capture_response_body: String,
+    // Status returned for captures to paused buckets (None = the normal capture response)
+    pub paused_capture_status: Option<StatusCode>,
+    // Polling subscriptions keyed by subscriber id
+    pub subscriptions: DashMap<String, Subscription>,
+    // Hosts that requests may be replayed to even though they resolve to non-public addresses
+    pub replay_allowed_hosts: Vec<String>,
+    // Accept the bucket password from a `password` query parameter when the header is absent
+    pub allow_query_password: bool,
+    // Record the client certificate subject forwarded by a TLS-terminating proxy
+    pub capture_client_cert: bool,
+    // Answer password-protected requests for missing buckets like a wrong password (401)
+    pub hide_bucket_existence: bool,
+    // Names that cannot be used for buckets (built-in defaults plus RESERVED_BUCKET_NAMES)
+    pub reserved_bucket_names: Vec<String>,
+    // Wakes long-polling readers when a bucket captures a request
+    pub capture_notifiers: DashMap<String, Arc<Notify>>,
+    // Every capture across all buckets, for the admin stream
+    pub capture_stream: broadcast::Sender<CaptureEvent>,
+    // Headers beyond this count are not captured
+    pub max_captured_headers: usize,
+    // Captured header values are cut to this many bytes
+    pub max_header_value_bytes: usize,
+    // Captured paths are cut to this many bytes (after the bucket has been resolved)
+    pub max_captured_path_bytes: usize,
+    // Stored body bytes across all buckets
+    pub total_bytes: AtomicUsize,
+    // Evict the oldest requests of the largest buckets once `total_bytes` exceeds this
+    pub global_max_bytes: Option<usize>,
+    // Token for the admin API, sent in the X-Admin-Token header (None = admin API disabled)
+    pub admin_token: Option<String>,
+    // Header carrying the bucket password (X-Bucket-Password unless PASSWORD_HEADER_NAME is set)
+    pub password_header: header::HeaderName,
+    // Externally reachable URL of this server, without a trailing slash (None = embeds disabled)
+    pub public_base_url: Option<String>,
+    // Replaces the built-in bucket name character rules; must match the whole name
+    pub bucket_name_pattern: Option<Regex>,
+    pub bucket_name_max_len: usize,
+    // Recently used create Idempotency-Keys
+    pub idempotency_keys: DashMap<String, IdempotentCreate>,
+    // Storage shared with other instances (e.g. Redis); None keeps buckets in memory only
+    pub shared_store: Option<Box<dyn SharedStore>>,
+    // Random source for `sample_rate` decisions (seed it for reproducible sampling)
+    pub sample_rng: Mutex<StdRng>,
+    // Set while buckets are being restored from a snapshot; captures and bucket access get 503
+    pub loading: AtomicBool,
+    // Time source for timestamps, password expiry and retention
+    pub clock: Arc<dyn Clock>,
+    // Longest a bucket's jq program may run on one body before it is stored untransformed
+    pub jq_filter_timeout: Duration,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        AppState {
+            buckets: DashMap::new(),
+            max_buckets: None,
+            creating_buckets: AtomicUsize::new(0),
+            auto_create_buckets: false,
+            regex_cache: RegexCache::default(),
+            event_sink: Box::new(NoopEventSink),
+            capture_response_status: StatusCode::OK,
+            capture_response_body: "Request captured".to_string(),
+            paused_capture_status: None,
+            subscriptions: DashMap::new(),
+            replay_allowed_hosts: Vec::new(),
+            allow_query_password: false,
+            capture_client_cert: false,
+            hide_bucket_existence: false,
+            reserved_bucket_names: DEFAULT_RESERVED_BUCKET_NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            capture_notifiers: DashMap::new(),
+            capture_stream: broadcast::channel(CAPTURE_STREAM_CAPACITY).0,
+            max_captured_headers: DEFAULT_MAX_CAPTURED_HEADERS,
+            max_header_value_bytes: DEFAULT_MAX_HEADER_VALUE_BYTES,
+            max_captured_path_bytes: DEFAULT_MAX_CAPTURED_PATH_BYTES,
+            total_bytes: AtomicUsize::new(0),
+            global_max_bytes: None,
+            admin_token: None,
+            bucket_name_pattern: None,
+            bucket_name_max_len: DEFAULT_BUCKET_NAME_MAX_LEN,
+            password_header: header::HeaderName::from_static(DEFAULT_PASSWORD_HEADER),
+            public_base_url: None,
+            idempotency_keys: DashMap::new(),
+            shared_store: None,
+            sample_rng: Mutex::new(StdRng::from_rng(&mut rand::rng())),
+            loading: AtomicBool::new(false),
+            clock: Arc::new(SystemClock),
+            jq_filter_timeout: DEFAULT_JQ_FILTER_TIMEOUT,
+        }
+    }
+}
+
+impl AppState {
+    /// Build the application state from environment variables, falling back to defaults.
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = AppState::default();
+        let mut reserved_bucket_names = defaults.reserved_bucket_names.clone();
+        if let Ok(extra) = env::var("RESERVED_BUCKET_NAMES") {
+            for name in extra.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                if !reserved_bucket_names
+                    .iter()
+                    .any(|existing| existing == name)
+                {
+                    reserved_bucket_names.push(name.to_string());
+                }
+            }
+        }
+
+        let replay_allowed_hosts = env::var("REPLAY_ALLOWED_HOSTS")
+            .map(|hosts| {
+                hosts
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|host| !host.is_empty())
+                    .map(str::to_ascii_lowercase)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(AppState {
+            max_buckets: parse_env_var("MAX_BUCKETS")?,
+            auto_create_buckets: parse_env_flag("AUTO_CREATE_BUCKETS"),
+            allow_query_password: parse_env_flag("ALLOW_QUERY_PASSWORD"),
+            capture_client_cert: parse_env_flag("CAPTURE_CLIENT_CERT"),
+            hide_bucket_existence: parse_env_flag("HIDE_BUCKET_EXISTENCE"),
+            reserved_bucket_names,
+            replay_allowed_hosts,
+            max_captured_headers: parse_env_var("MAX_CAPTURED_HEADERS")?
+                .unwrap_or(DEFAULT_MAX_CAPTURED_HEADERS),
+            max_header_value_bytes: parse_env_var("MAX_HEADER_VALUE_BYTES")?
+                .unwrap_or(DEFAULT_MAX_HEADER_VALUE_BYTES),
+            max_captured_path_bytes: parse_env_var("MAX_CAPTURED_PATH_BYTES")?
+                .unwrap_or(DEFAULT_MAX_CAPTURED_PATH_BYTES),
+            event_sink: events::sink_from_env()?,
+            shared_store: store::store_from_env()?,
+            capture_response_status: parse_status_env_var("CAPTURE_RESPONSE_STATUS")?
+                .unwrap_or(StatusCode::OK),
+            paused_capture_status: parse_status_env_var("PAUSED_CAPTURE_STATUS")?,
+            capture_response_body: env::var("CAPTURE_RESPONSE_BODY")
+                .unwrap_or_else(|_| "Request captured".to_string()),
+            global_max_bytes: parse_env_var("GLOBAL_MAX_BYTES")?,
+            admin_token: env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            bucket_name_pattern: parse_bucket_name_pattern()?,
+            password_header: parse_password_header()?,
+            public_base_url: parse_public_base_url()?,
+            bucket_name_max_len: parse_env_var("BUCKET_NAME_MAX_LEN")?
+                .unwrap_or(DEFAULT_BUCKET_NAME_MAX_LEN),
+            ..defaults
+        })
+    }
+
+    // Reserve room for one more bucket within `max_buckets`, or None when the limit is reached.
+    // Creations still in progress count too, so concurrent creates can't go past the limit;
+    // drop the slot once the bucket has been inserted (or wasn't)
+    fn reserve_bucket_slot(&self) -> Option<BucketSlot<'_>> {
+        let max_buckets = self.max_buckets.unwrap_or(usize::MAX);
+        self.creating_buckets
+            .fetch_update(
+                AtomicOrdering::AcqRel,
+                AtomicOrdering::Acquire,
+                |creating| {
+                    (self.buckets.len().saturating_add(creating) < max_buckets)
+                        .then_some(creating + 1)
+                },
+            )
+            .ok()
+            .map(|_| BucketSlot(&self.creating_buckets))
+    }
+
+    // Apply the bucket's age-based retention, keeping `total_bytes` in step. Returns the `seq`s
+    // of the expired requests
+    fn expire_requests(&self, bucket: &mut Bucket) -> Vec<u64> {
+        let stored_before = bucket.stored_bytes;
+        let expired = bucket.evict_expired(self.clock.now_millis());
+        self.track_stored_bytes(stored_before, bucket.stored_bytes);
+        expired
+    }
+
+    // Account for a bucket's stored bytes changing from `before` to `after`
+    fn track_stored_bytes(&self, before: usize, after: usize) {
+        if after > before {
+            self.total_bytes
+                .fetch_add(after - before, AtomicOrdering::Relaxed);
+        } else {
+            self.total_bytes
+                .fetch_sub(before - after, AtomicOrdering::Relaxed);
+        }
+    }
+
+    // Hand a newly stored request to the event sink and the live capture stream
+    fn broadcast_capture(&self, bucket_name: &str, request: &RequestData) {
+        self.event_sink.publish(bucket_name, request);
+        // Sending never waits: subscribers that fall behind lose the oldest captures instead
+        if self.capture_stream.receiver_count() > 0 {
+            let _ = self.capture_stream.send(CaptureEvent {
+                bucket: bucket_name.to_string(),
+                request: request.clone(),
+            });
+        }
+    }
+
+    // Evict the oldest requests of the largest buckets until `total_bytes` is within GLOBAL_MAX_BYTES
+    fn enforce_global_max_bytes(&self) {
+        let Some(global_max_bytes) = self.global_max_bytes else {
+            return;
+        };
+        while self.total_bytes.load(AtomicOrdering::Relaxed) > global_max_bytes {
+            let largest = self
+                .buckets
+                .iter()
+                .filter(|entry| entry.stored_bytes > 0)
+                .max_by_key(|entry| entry.stored_bytes)
+                .map(|entry| entry.key().clone());
+            let Some(name) = largest else {
+                break;
+            };
+            if let Some(mut bucket_ref) = self.buckets.get_mut(&name) {
+                let freed = bucket_ref.evict_oldest();
+                self.total_bytes.fetch_sub(freed, AtomicOrdering::Relaxed);
+            }
+        }
+    }
+}
+
+// Room for a bucket reserved with `AppState::reserve_bucket_slot`
+struct BucketSlot<'a>(&'a AtomicUsize);
+
+impl Drop for BucketSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, AtomicOrdering::AcqRel);
+    }
+}
+
+// Helper function to refresh the local copy of a bucket from the shared store, if one is configured
+async fn sync_bucket(app_state: &AppState, bucket_name: &str) {
+    let Some(store) = &app_state.shared_store else {
+        return;
+    };
+    // Handlers sync before authenticating, so every sync makes the same store calls whether or
+    // not the bucket exists or had requests expire
+    match store.load_bucket(bucket_name).await {
+        Ok(Some(mut bucket)) => {
+            // Retention is applied to the shared copy too, so both hold the same requests
+            let expired = bucket.evict_expired(app_state.clock.now_millis());
+            finish_store_writes([store.remove_requests(bucket_name, &expired)]).await;
+            let stored_after = bucket.stored_bytes;
+            let stored_before = app_state
+                .buckets
+                .insert(bucket_name.to_string(), bucket)
+                .map_or(0, |old| old.stored_bytes);
+            app_state.track_stored_bytes(stored_before, stored_after);
+        }
+        Ok(None) => {
+            finish_store_writes([store.remove_requests(bucket_name, &[])]).await;
+            if let Some((_, old)) = app_state.buckets.remove(bucket_name) {
+                app_state
+                    .total_bytes
+                    .fetch_sub(old.stored_bytes, AtomicOrdering::Relaxed);
+            }
+        }
+        Err(e) => warn!(error = %e, "Failed to load bucket from shared store, using local copy"),
+    }
+}
+
+// Helper function to wait for shared store writes (built while the bucket was locked). Failures
+// are logged and the local change is kept
+async fn finish_store_writes<'a>(writes: impl IntoIterator<Item = StoreFuture<'a, ()>>) {
+    for write in writes {
+        if let Err(e) = write.await {
+            warn!(error = %e, "Failed to write to shared store");
+        }
+    }
+}
+
+/// Output format for log events, selected with the `LOG_FORMAT` environment variable.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    /// Resolve the log format from the raw `LOG_FORMAT` value (unset means `text`).
+    pub fn from_env_value(value: Option<&str>) -> Result<Self, String> {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("text") => Ok(LogFormat::Text),
+            Some("json") => Ok(LogFormat::Json),
+            Some(other) => Err(format!(
+                "Invalid value for LOG_FORMAT: {} (expected text or json)",
+                other
+            )),
+        }
+    }
+}
+
+/// Where the server listens: a Unix socket from `LISTEN_UDS`, otherwise `HOST:PORT` over TCP.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ListenAddress {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl ListenAddress {
+    /// Resolve the listen address from the raw `LISTEN_UDS`, `HOST` and `PORT` values.
+    pub fn from_env_values(uds: Option<&str>, host: Option<&str>, port: Option<&str>) -> Self {
+        match uds.filter(|path| !path.is_empty()) {
+            Some(path) => ListenAddress::Unix(PathBuf::from(path)),
+            None => ListenAddress::Tcp(format!(
+                "{}:{}",
+                host.unwrap_or("127.0.0.1"),
+                port.unwrap_or("9090")
+            )),
+        }
+    }
+}
+
+/// Where the UI is served from: `STATIC_DIR` (default `./static`) with `INDEX_FILE` (default
+/// `index.html`) as its index page.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StaticFiles {
+    pub dir: PathBuf,
+    pub index_file: String,
+}
+
+impl StaticFiles {
+    /// Resolve the UI mount from the raw `STATIC_DIR` and `INDEX_FILE` values.
+    pub fn from_env_values(dir: Option<&str>, index_file: Option<&str>) -> Self {
+        StaticFiles {
+            dir: PathBuf::from(dir.filter(|dir| !dir.is_empty()).unwrap_or("./static")),
+            index_file: index_file
+                .filter(|file| !file.is_empty())
+                .unwrap_or("index.html")
+                .to_string(),
+        }
+    }
+}
+
+// Helper function to read PASSWORD_HEADER_NAME, which must be a valid HTTP header name
+fn parse_password_header() -> Result<header::HeaderName, String> {
+    match parse_env_var::<String>("PASSWORD_HEADER_NAME")? {
+        Some(name) => header::HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("Invalid value for PASSWORD_HEADER_NAME: {}", name)),
+        None => Ok(header::HeaderName::from_static(DEFAULT_PASSWORD_HEADER)),
+    }
+}
+
+// Helper function to check that a URL is an absolute http(s) URL
+fn is_http_url(url: &Uri) -> bool {
+    matches!(url.scheme_str(), Some("http" | "https")) && url.host().is_some()
+}
+
+// Helper function to read PUBLIC_BASE_URL, which must be an http(s) URL
+fn parse_public_base_url() -> Result<Option<String>, String> {
+    parse_env_var::<String>("PUBLIC_BASE_URL")?
+        .map(|value| match value.parse::<Uri>() {
+            Ok(url) if is_http_url(&url) => Ok(value.trim_end_matches('/').to_string()),
+            _ => Err(format!("Invalid value for PUBLIC_BASE_URL: {}", value)),
+        })
+        .transpose()
 }
 
-// Helper function to extract bucket name from path
-fn extract_bucket_name(path: &str) -> Option<&str> {
-    path.trim_start_matches('/')
-        .split('/')
-        .next()
-        .filter(|name| !name.is_empty())
-}
+// Helper function to compile BUCKET_NAME_PATTERN, anchored so it must match the whole name
+fn parse_bucket_name_pattern() -> Result<Option<Regex>, String> {
+    parse_env_var::<String>("BUCKET_NAME_PATTERN")?
+        .map(|pattern| {
+            Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| format!("Invalid value for BUCKET_NAME_PATTERN: {}", e))
+        })
+        .transpose()
+}
+
+// Helper function to read a status code environment variable, which must be a final (2xx-5xx)
+// status code
+fn parse_status_env_var(name: &str) -> Result<Option<StatusCode>, String> {
+    match parse_env_var::<u16>(name)? {
+        Some(code) if (200..=599).contains(&code) => StatusCode::from_u16(code)
+            .map(Some)
+            .map_err(|_| format!("Invalid value for {}: {}", name, code)),
+        Some(code) => Err(format!(
+            "{} must be between 200 and 599, got {}",
+            name, code
+        )),
+        None => Ok(None),
+    }
+}
+
+// Helper function to check whether any media type in the Accept header is `application/json`
+fn accepts_json(req: &HttpRequest) -> bool {
+    req.headers()
+        .get_all(header::ACCEPT)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|media_type| {
+            let essence = media_type.split(';').next().unwrap_or_default().trim();
+            essence.eq_ignore_ascii_case("application/json")
+        })
+}
+
+// Helper function to read the capture response a sender asked for with `X-Mock-Status` (a final
+// 2xx-5xx status) and `X-Mock-Body`; None when no status was requested
+fn requested_mock_response(req: &HttpRequest) -> Result<Option<(StatusCode, String)>, String> {
+    let Some(status) = req.headers().get(MOCK_STATUS_HEADER) else {
+        return Ok(None);
+    };
+    let status = status
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().parse::<u16>().ok())
+        .filter(|code| (200..=599).contains(code))
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .ok_or_else(|| {
+            format!(
+                "{} must be a status code between 200 and 599",
+                MOCK_STATUS_HEADER
+            )
+        })?;
+    let body = req
+        .headers()
+        .get(MOCK_BODY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    Ok(Some((status, body)))
+}
+
+// Helper function to parse an optional environment variable into the requested type
+fn parse_env_var<T: FromStr>(name: &str) -> Result<Option<T>, String> {
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid value for {}: {}", name, value)),
+        _ => Ok(None),
+    }
+}
+
+// Helper function to read a boolean flag from the environment (1/true/yes/on)
+fn parse_env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(false)
+}
+
+// Helper function to check whether the client asked for indented JSON, with `?pretty=true` or an
+// `X-Pretty` header (1/true/yes/on)
+fn wants_pretty_json(req: &HttpRequest, pretty_param: bool) -> bool {
+    pretty_param
+        || req
+            .headers()
+            .get("x-pretty")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|value| {
+                matches!(
+                    value.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "on"
+                )
+            })
+}
+
+// Helper function to finish a JSON response, indented for humans when `pretty` is set
+fn json_response<T: Serialize>(
+    builder: &mut HttpResponseBuilder,
+    value: &T,
+    pretty: bool,
+) -> HttpResponse {
+    if pretty {
+        if let Ok(body) = serde_json::to_string_pretty(value) {
+            return builder.content_type("application/json").body(body);
+        }
+    }
+    builder.json(value)
+}
+
+// Helper function to extract bucket name from path
+fn extract_bucket_name(path: &str) -> Option<&str> {
+    path.trim_start_matches('/')
+        .split('/')
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+// Helper function to split the subpath (everything after the bucket name) into decoded segments
+fn extract_path_segments(path: &str) -> Vec<String> {
+    path.trim_start_matches('/')
+        .split('/')
+        .skip(1)
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+        .collect()
+}
+
+// Helper function to extract the password from an `Authorization: Basic` header (the
+// username is ignored)
+fn basic_auth_password(req: &HttpRequest) -> Option<String> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(credentials.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (_, password) = decoded.split_once(':')?;
+    Some(password.to_string())
+}
+
+/// Machine-readable error codes, one per HTTP error status the API returns.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    PayloadTooLarge,
+    UnprocessableEntity,
+    TooManyRequests,
+    BadGateway,
+    ServiceUnavailable,
+    InsufficientStorage,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::UnprocessableEntity => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::BadGateway => StatusCode::BAD_GATEWAY,
+            ErrorCode::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::InsufficientStorage => StatusCode::INSUFFICIENT_STORAGE,
+        }
+    }
+}
+
+// Body of every error response
+#[derive(Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: ErrorCode,
+    pub message: String,
+}
+
+impl ErrorResponse {
+    fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ErrorResponse {
+            error: code,
+            message: message.into(),
+        }
+    }
+
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::build(self.error.status()).json(self)
+    }
+}
+
+// Helper function to build a JSON error response with the status matching `code`
+fn error_response(code: ErrorCode, message: impl Into<String>) -> HttpResponse {
+    ErrorResponse::new(code, message).into_response()
+}
+
+/// Error handler for `web::JsonConfig`, so malformed JSON bodies get the standard error body.
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = match &err {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            error_response(ErrorCode::PayloadTooLarge, "JSON body too large")
+        }
+        _ => error_response(ErrorCode::BadRequest, format!("Invalid JSON body: {}", err)),
+    };
+    warn!(error = %err, "Rejected malformed JSON body");
+    InternalError::from_response(err, response).into()
+}
+
+// Helper function to extract the password from the request header, falling back to Basic
+// auth and then to the `password` query parameter when ALLOW_QUERY_PASSWORD is enabled
+#[allow(clippy::result_large_err)]
+fn get_request_password<'a>(
+    req: &'a HttpRequest,
+    app_state: &AppState,
+) -> Result<Cow<'a, str>, HttpResponse> {
+    if let Some(p) = req.headers().get(&app_state.password_header) {
+        return Ok(Cow::Borrowed(p.to_str().unwrap_or("")));
+    }
+    if let Some(p) = basic_auth_password(req) {
+        return Ok(Cow::Owned(p));
+    }
+
+    let query_password = app_state
+        .allow_query_password
+        .then(|| web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok())
+        .flatten()
+        .and_then(|query| query.into_inner().remove("password"));
+    match query_password {
+        Some(p) => Ok(Cow::Owned(p)),
+        None => {
+            warn!("Password header missing");
+            Err(error_response(ErrorCode::Unauthorized, "Password required"))
+        }
+    }
+}
+
+// Helper function to verify password against bucket
+fn verify_bucket_password(bucket: &Bucket, password: &str) -> bool {
+    bucket.password.as_bytes().ct_eq(password.as_bytes()).into()
+}
+
+// Helper function to answer a password-protected request for a bucket that doesn't exist. With
+// HIDE_BUCKET_EXISTENCE the password is compared against a placeholder just as it would be
+// against a real bucket, and the response is the one for a wrong password
+fn missing_bucket_response(app_state: &AppState, password: &str) -> HttpResponse {
+    missing_bucket_error(app_state, password).into_response()
+}
+
+// Helper function to pick the error for a missing bucket (see `missing_bucket_response`)
+fn missing_bucket_error(app_state: &AppState, password: &str) -> ErrorResponse {
+    warn!("Request for non-existent bucket");
+    if app_state.hide_bucket_existence {
+        std::hint::black_box(bool::from(
+            MISSING_BUCKET_PASSWORD
+                .as_bytes()
+                .ct_eq(password.as_bytes()),
+        ));
+        return ErrorResponse::new(ErrorCode::Unauthorized, "Invalid password");
+    }
+    ErrorResponse::new(ErrorCode::NotFound, "Bucket not found")
+}
+
+// Helper function to check whether the bucket's password has passed its expiry as of `now` (ms)
+fn is_password_expired(bucket: &Bucket, now: i64) -> bool {
+    bucket
+        .password_expires_at
+        .is_some_and(|expires_at| now >= expires_at)
+}
+
+// Helper function to authorize access to a bucket's data (password and expiry policy)
+#[allow(clippy::result_large_err)]
+fn authorize_bucket(bucket: &Bucket, password: &str, now: i64) -> Result<(), HttpResponse> {
+    check_bucket_access(bucket, password, now).map_err(ErrorResponse::into_response)
+}
+
+// Helper function to check the password and expiry policy, describing why access is refused
+fn check_bucket_access(bucket: &Bucket, password: &str, now: i64) -> Result<(), ErrorResponse> {
+    if !verify_bucket_password(bucket, password) {
+        warn!("Invalid password provided for bucket");
+        return Err(ErrorResponse::new(
+            ErrorCode::Unauthorized,
+            "Invalid password",
+        ));
+    }
+    if is_password_expired(bucket, now) {
+        warn!("Expired password provided for bucket");
+        return Err(ErrorResponse::new(
+            ErrorCode::Forbidden,
+            "Password has expired. Rotate the password via /api/rotate/{bucket_name}",
+        ));
+    }
+    Ok(())
+}
+
+// Helper function to derive a bucket's read-only embed token (changes when the password rotates)
+fn embed_token(bucket_name: &str, password: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(password.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(b"embed:");
+    mac.update(bucket_name.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Helper function to check a read-only embed token against a bucket
+fn verify_embed_token(bucket_name: &str, bucket: &Bucket, token: &str, now: i64) -> bool {
+    let expected = embed_token(bucket_name, &bucket.password);
+    let valid: bool = expected.as_bytes().ct_eq(token.as_bytes()).into();
+    valid && !is_password_expired(bucket, now)
+}
+
+// Helper function to check an embed token for a bucket that doesn't exist the same way as for a
+// real one (against a placeholder key), so the time taken doesn't give the bucket away
+fn verify_missing_bucket_embed_token(bucket_name: &str, token: &str) {
+    let expected = embed_token(bucket_name, MISSING_BUCKET_PASSWORD);
+    std::hint::black_box(bool::from(expected.as_bytes().ct_eq(token.as_bytes())));
+}
+
+// Helper function to turn requests away until buckets have been restored from a snapshot
+#[allow(clippy::result_large_err)]
+fn ensure_loaded(app_state: &AppState) -> Result<(), HttpResponse> {
+    if app_state.loading.load(AtomicOrdering::Acquire) {
+        warn!("Request received while buckets are still loading");
+        return Err(error_response(
+            ErrorCode::ServiceUnavailable,
+            "Buckets are still being restored, try again shortly",
+        ));
+    }
+    Ok(())
+}
+
+// Helper function to check the X-Admin-Token header against ADMIN_TOKEN
+#[allow(clippy::result_large_err)]
+fn authenticate_admin(req: &HttpRequest, app_state: &AppState) -> Result<(), HttpResponse> {
+    let Some(admin_token) = app_state.admin_token.as_deref() else {
+        warn!("Admin API called but ADMIN_TOKEN is not set");
+        return Err(error_response(ErrorCode::NotFound, "Admin API is disabled"));
+    };
+    let provided = req
+        .headers()
+        .get(ADMIN_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if bool::from(admin_token.as_bytes().ct_eq(provided.as_bytes())) {
+        Ok(())
+    } else {
+        warn!("Invalid admin token");
+        Err(error_response(
+            ErrorCode::Unauthorized,
+            "Invalid admin token",
+        ))
+    }
+}
+
+// Helper function to look up a bucket with a read-only embed token
+#[allow(clippy::result_large_err)]
+fn authenticate_bucket_token<'a>(
+    app_state: &'a AppState,
+    bucket_name: &str,
+    token: &str,
+) -> Result<Ref<'a, String, Bucket>, HttpResponse> {
+    ensure_loaded(app_state)?;
+    let now = app_state.clock.now_millis();
+    match app_state.buckets.get_mut(bucket_name) {
+        Some(mut bucket_ref) if verify_embed_token(bucket_name, &bucket_ref, token, now) => {
+            app_state.expire_requests(&mut bucket_ref);
+            Ok(bucket_ref.downgrade())
+        }
+        Some(_) => {
+            warn!("Invalid embed token provided for bucket");
+            Err(error_response(ErrorCode::Forbidden, "Invalid token"))
+        }
+        None if app_state.hide_bucket_existence => {
+            warn!("Request for non-existent bucket");
+            verify_missing_bucket_embed_token(bucket_name, token);
+            Err(error_response(ErrorCode::Forbidden, "Invalid token"))
+        }
+        None => {
+            warn!("Request for non-existent bucket");
+            Err(error_response(ErrorCode::NotFound, "Bucket not found"))
+        }
+    }
+}
+
+// Helper function to authorize read access with the password header, or an embed token if given instead
+#[allow(clippy::result_large_err)]
+fn authenticate_bucket_read<'a>(
+    req: &HttpRequest,
+    app_state: &'a AppState,
+    bucket_name: &str,
+    token: Option<&str>,
+) -> Result<Ref<'a, String, Bucket>, HttpResponse> {
+    match token {
+        Some(token) if req.headers().get(&app_state.password_header).is_none() => {
+            authenticate_bucket_token(app_state, bucket_name, token)
+        }
+        _ => authenticate_bucket(req, app_state, bucket_name),
+    }
+}
+
+// Helper function to look up a bucket and authorize the request against it
+#[allow(clippy::result_large_err)]
+fn authenticate_bucket<'a>(
+    req: &HttpRequest,
+    app_state: &'a AppState,
+    bucket_name: &str,
+) -> Result<Ref<'a, String, Bucket>, HttpResponse> {
+    ensure_loaded(app_state)?;
+    let password = get_request_password(req, app_state)?;
+    match app_state.buckets.get_mut(bucket_name) {
+        Some(mut bucket_ref) => {
+            authorize_bucket(&bucket_ref, &password, app_state.clock.now_millis())?;
+            app_state.expire_requests(&mut bucket_ref);
+            Ok(bucket_ref.downgrade())
+        }
+        None => Err(missing_bucket_response(app_state, &password)),
+    }
+}
+
+// Helper function to look up a bucket for modification and authorize the request against it
+#[allow(clippy::result_large_err)]
+fn authenticate_bucket_mut<'a>(
+    req: &HttpRequest,
+    app_state: &'a AppState,
+    bucket_name: &str,
+) -> Result<RefMut<'a, String, Bucket>, HttpResponse> {
+    ensure_loaded(app_state)?;
+    let password = get_request_password(req, app_state)?;
+    match app_state.buckets.get_mut(bucket_name) {
+        Some(mut bucket_ref) => {
+            authorize_bucket(&bucket_ref, &password, app_state.clock.now_millis())?;
+            app_state.expire_requests(&mut bucket_ref);
+            Ok(bucket_ref)
+        }
+        None => Err(missing_bucket_response(app_state, &password)),
+    }
+}
+
+// Helper function to check whether a captured request carried a header, optionally with a
+// specific value (both compared case-insensitively)
+fn has_header(request: &RequestData, name: &str, value: Option<&str>) -> bool {
+    request.headers.iter().any(|(header_name, header_value)| {
+        header_name.eq_ignore_ascii_case(name)
+            && value.is_none_or(|value| header_value.eq_ignore_ascii_case(value))
+    })
+}
+
+// Helper function to compute a weak ETag from the bucket's request count and last timestamp, and
+// the listing's query string so differently filtered or paged listings don't share an ETag
+fn bucket_etag(bucket: &Bucket, query_string: &str) -> String {
+    let last_timestamp = bucket.requests.back().map_or(0, |r| r.timestamp);
+    format!(
+        "W/\"{}-{}-{}-{}\"",
+        bucket.requests.len(),
+        last_timestamp,
+        bucket.revision,
+        query_hash(query_string)
+    )
+}
+
+// Helper function to hash a query string independently of parameter order and encoding. Parameters
+// that don't change the listing (credentials and long-poll waits) are left out
+fn query_hash(query_string: &str) -> String {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let mut pairs = web::Query::<Vec<(String, String)>>::from_query(query_string)
+        .map(|query| query.into_inner())
+        .unwrap_or_else(|_| vec![(query_string.to_string(), String::new())]);
+    pairs.retain(|(name, _)| !matches!(name.as_str(), "password" | "token" | "wait_ms"));
+    pairs.sort();
+    let mut hasher = DefaultHasher::new();
+    pairs.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+// Helper function to check the If-None-Match header against an ETag (weak comparison)
+fn etag_matches(req: &HttpRequest, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    req.headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|value| {
+            value.split(',').any(|candidate| {
+                let candidate = candidate.trim();
+                candidate == "*" || candidate.trim_start_matches("W/") == etag
+            })
+        })
+        .unwrap_or(false)
+}
+
+// Helper function to resolve a dotted JSON path (e.g. `order.amount` or `items.0.price`) in a request body
+fn body_sort_key(body: &str, path: &str) -> Option<SortKey> {
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    let value = path.split('.').try_fold(&json, |value, key| match value {
+        serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => value.get(key),
+    })?;
+    match value {
+        serde_json::Value::Number(n) => n.as_f64().map(SortKey::Number),
+        serde_json::Value::String(s) => Some(SortKey::Text(s.clone())),
+        _ => None,
+    }
+}
+
+// Helper function to compute the `start..end` positions of a page (1-based), clamped to `total`
+fn page_bounds(total: usize, page: usize, page_size: usize) -> (usize, usize) {
+    let start = page.saturating_sub(1).saturating_mul(page_size).min(total);
+    (start, (start + page_size).min(total))
+}
+
+// Helper function to sort requests by a JSON body field; requests without the field sort last
+fn sort_by_body_field(requests: &mut Vec<&RequestData>, path: &str, direction: SortDirection) {
+    let mut keyed: Vec<(Option<SortKey>, &RequestData)> = requests
+        .iter()
+        .map(|r| (body_sort_key(&r.body, path), *r))
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) if direction == SortDirection::Desc => b.cmp(a),
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    *requests = keyed.into_iter().map(|(_, r)| r).collect();
+}
+
+// Helper function to cut a string to at most `max_bytes` without splitting a character,
+// reporting whether anything was removed
+fn truncate_at_char_boundary(value: &mut String, max_bytes: usize) -> bool {
+    if value.len() <= max_bytes {
+        return false;
+    }
+    let mut end = max_bytes;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    value.truncate(end);
+    true
+}
+
+// Helper function to collect request headers within the count and value size limits,
+// reporting whether anything was dropped or shortened
+fn capture_headers(
+    req: &HttpRequest,
+    max_headers: usize,
+    max_value_bytes: usize,
+) -> (HashMap<String, String>, bool) {
+    let mut headers = HashMap::new();
+    let mut truncated = false;
+    for (name, value) in req.headers() {
+        if headers.len() >= max_headers && !headers.contains_key(name.as_str()) {
+            truncated = true;
+            continue;
+        }
+        let mut value = value.to_str().unwrap_or("").to_string();
+        truncated |= truncate_at_char_boundary(&mut value, max_value_bytes);
+        headers.insert(name.as_str().to_string(), value);
+    }
+    (headers, truncated)
+}
+
+// Helper function to get the request's origin from the Origin header, falling back to Referer
+fn request_origin(req: &HttpRequest) -> Option<String> {
+    let header_value = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    if let Some(origin) = header_value(header::ORIGIN) {
+        return Some(origin.trim_end_matches('/').to_string());
+    }
+
+    // Reduce the referer URL to `scheme://host[:port]`
+    let referer = header_value(header::REFERER)?;
+    let (scheme, rest) = referer.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    Some(format!("{}://{}", scheme, host))
+}
+
+// Helper function to get the externally visible `scheme://host` this server was reached on
+fn request_base_url(req: &HttpRequest) -> String {
+    let connection_info = req.connection_info();
+    format!("{}://{}", connection_info.scheme(), connection_info.host())
+}
+
+// Helper function to get the host a request was addressed to
+fn request_host(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| req.uri().authority().map(|authority| authority.to_string()))
+}
+
+// Helper function to re-serialize a JSON document compactly (None if it isn't valid JSON)
+fn minify_json(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    serde_json::to_string(&value).ok()
+}
+
+// Helper function to check whether a content type is JSON (`application/json` or `*/*+json`)
+fn is_json_content_type(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.eq_ignore_ascii_case("application/json") || mime.to_ascii_lowercase().ends_with("+json")
+}
+
+// Helper function to replace a serialized request's `body` string with the parsed JSON it contains
+fn parse_json_body(request: &mut serde_json::Value) {
+    let is_json = request["headers"]["content-type"]
+        .as_str()
+        .is_some_and(is_json_content_type);
+    if !is_json {
+        return;
+    }
+    let parsed = request["body"]
+        .as_str()
+        .and_then(|body| serde_json::from_str::<serde_json::Value>(body).ok());
+    if let Some(parsed) = parsed {
+        request["body"] = parsed;
+    }
+}
+
+// Helper function to drop a serialized request's `body` (and `raw_body`/`body_base64`), leaving
+// its `body_length`
+fn strip_body(request: &mut serde_json::Value) {
+    if let Some(request) = request.as_object_mut() {
+        request.remove("raw_body");
+        request.remove("body_base64");
+        let body_length = request
+            .remove("body")
+            .and_then(|body| body.as_str().map(str::len))
+            .unwrap_or_default();
+        // Requests stored before `body_length` was recorded have it as 0
+        let recorded = request.get("body_length").and_then(|v| v.as_u64());
+        if body_length > 0 && recorded.unwrap_or_default() == 0 {
+            request.insert("body_length".to_string(), body_length.into());
+        }
+    }
+}
+
+// Helper function to escape text for inclusion in HTML
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// Helper function to parse query parameters
+fn parse_query_params(query_string: &str) -> HashMap<String, String> {
+    if query_string.is_empty() {
+        return HashMap::new();
+    }
+
+    query_string
+        .split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if !key.is_empty() => {
+                    Some((key.to_string(), value.to_string()))
+                }
+                (Some(key), None) if !key.is_empty() => Some((key.to_string(), String::new())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct CreateBucketPayload {
+    pub password: String,
+    #[serde(flatten)]
+    pub config: BucketConfig,
+    // Requests to seed the bucket with, e.g. as test fixtures (subject to the per-bucket limits)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requests: Vec<RequestData>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct ReplaceBucketPayload {
+    #[serde(flatten)]
+    pub bucket: CreateBucketPayload,
+    // Also delete the stored requests when replacing an existing bucket
+    #[serde(default)]
+    pub clear: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ImportBucketPayload {
+    pub password: String,
+    #[serde(flatten)]
+    pub bucket: BucketExport,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PasswordExpiryPayload {
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RotatePasswordPayload {
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct PaginationParams {
+    pub page: Option<usize>,
+    pub page_size: Option<usize>,
+    // Read-only embed token, accepted instead of the password header
+    pub token: Option<String>,
+    // Only include requests whose path matches this regular expression
+    pub path_regex: Option<String>,
+    // Dotted JSON path into the body to sort by (e.g. `order.amount`)
+    pub sort_by: Option<String>,
+    #[serde(default)]
+    pub sort_dir: SortDirection,
+    // Return JSON bodies as parsed objects instead of strings, and indent the response
+    #[serde(default)]
+    pub pretty: bool,
+    // Only include requests captured after this time (milliseconds since the Unix epoch)
+    pub after_timestamp: Option<i64>,
+    // Long-poll: wait up to this long for a request newer than `after_timestamp` before responding
+    pub wait_ms: Option<u64>,
+    // `meta` leaves out each request's body (its size stays in `body_length`)
+    pub fields: Option<RequestFields>,
+    // Only include requests carrying this tag
+    pub tag: Option<String>,
+    // Only include requests captured on this channel (first path segment after the bucket name)
+    pub channel: Option<String>,
+    // Only include requests carrying this header (name compared case-insensitively)
+    pub header_name: Option<String>,
+    // With `header_name`, only include requests whose header has this value (case-insensitive)
+    pub header_value: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BatchRequestsPayload {
+    pub buckets: Vec<String>,
+    // Password per bucket; buckets without one use the password sent with the request
+    #[serde(default)]
+    pub passwords: HashMap<String, String>,
+    // Latest requests returned per bucket
+    pub limit: Option<usize>,
+}
+
+// One bucket's part of a batch fetch: its latest requests, or why they couldn't be read
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum BatchBucketResult {
+    Requests {
+        requests: Vec<RequestData>,
+        total: usize,
+    },
+    Error(ErrorResponse),
+}
+
+impl From<ErrorResponse> for BatchBucketResult {
+    fn from(error: ErrorResponse) -> Self {
+        BatchBucketResult::Error(error)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MethodGroupParams {
+    // Paginate each method's requests; without either all of them are returned
+    pub page: Option<usize>,
+    pub page_size: Option<usize>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestFields {
+    Meta,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+// Value a request is sorted by when `sort_by` is used (numbers order before strings)
+enum SortKey {
+    Number(f64),
+    Text(String),
+}
+
+impl SortKey {
+    fn cmp(&self, other: &SortKey) -> Ordering {
+        match (self, other) {
+            (SortKey::Number(a), SortKey::Number(b)) => a.total_cmp(b),
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+            (SortKey::Number(_), SortKey::Text(_)) => Ordering::Less,
+            (SortKey::Text(_), SortKey::Number(_)) => Ordering::Greater,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PaginatedResponse {
+    pub requests: Vec<RequestData>,
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+    pub total_pages: usize,
+    pub watermark: String,
+    // Captures dropped by the bucket's `capture_content_types` filter
+    pub dropped_count: u64,
+    // Captures not kept because of the bucket's `sample_rate`
+    pub sampled_out_count: u64,
+    // Captures not kept because of the bucket's `ignore_user_agents`
+    pub ignored_count: u64,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Curl,
+}
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    pub format: ExportFormat,
+}
+
+#[derive(Deserialize)]
+pub struct DiffParams {
+    pub a: usize,
+    pub b: usize,
+}
+
+#[derive(Deserialize)]
+pub struct ExtractParams {
+    // JSONPath expression applied to each JSON body, e.g. `$.user.id`
+    pub path: String,
+}
+
+// A value matched by a JSONPath expression in the body of the request at `index`
+#[derive(Serialize, Deserialize)]
+pub struct ExtractMatch {
+    pub index: usize,
+    pub value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+pub struct EmbedParams {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EmbedTokenResponse {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReplayPayload {
+    pub target_url: String,
+}
+
+// A bucket's settings as returned by the config endpoint (never includes the password)
+#[derive(Serialize, Deserialize)]
+pub struct BucketSettings {
+    #[serde(flatten)]
+    pub config: BucketConfig,
+    // False while capture is paused
+    pub enabled: bool,
+    pub password_expires_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StorageReport {
+    pub total_bytes: usize,
+    pub buckets: usize,
+}
+
+#[derive(Deserialize)]
+pub struct ListBucketsParams {
+    // Only list buckets whose name starts with this
+    pub prefix: Option<String>,
+    // Indent the response for reading
+    #[serde(default)]
+    pub pretty: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PrettyParams {
+    // Indent the response for reading
+    #[serde(default)]
+    pub pretty: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    // Case-insensitive substring looked for in paths, bodies and headers
+    pub q: String,
+}
+
+// A request matching an admin search, identified by bucket and index
+#[derive(Serialize, Deserialize)]
+pub struct SearchResult {
+    pub bucket: String,
+    pub index: usize,
+    pub request: RequestData,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ResetResponse {
+    pub removed_buckets: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PurgeResponse {
+    // Names of the removed buckets, sorted
+    pub removed_buckets: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TagPayload {
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReplayResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PathCount {
+    pub path: String,
+    pub count: usize,
+}
+
+// How a header varies across the stored requests of a bucket
+#[derive(Serialize, Deserialize)]
+pub struct HeaderSummary {
+    pub distinct_values: usize,
+    // Value of the header in the oldest stored request that has it
+    pub sample: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SubscribeResponse {
+    pub subscriber_id: String,
+}
+
+// A capture as sent on the admin stream
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CaptureEvent {
+    pub bucket: String,
+    pub request: RequestData,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PollResponse {
+    pub requests: Vec<RequestData>,
+    // True when captures were cleared or evicted since the last poll; `requests` then holds everything stored
+    pub resync: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SinceParams {
+    pub watermark: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SinceResponse {
+    pub requests: Vec<RequestData>,
+    pub watermark: String,
+    // True when the watermark can no longer be honoured and the client must refetch everything
+    pub resync: bool,
+}
+
+// Helper function to check that `replay_from_index` points at one of the bucket's requests
+fn validate_replay_index(config: &BucketConfig, request_count: usize) -> Result<(), String> {
+    match config.replay_from_index {
+        Some(index) if index >= request_count => Err(format!(
+            "replay_from_index {} is out of range for a bucket with {} requests",
+            index, request_count
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Helper function to validate requests seeded at bucket creation, recomputing the fields the
+// server derives so seeds can't claim a body hash, length or `seq` they don't have
+fn prepare_seed_requests(requests: &mut [RequestData]) -> Result<(), String> {
+    for (index, request) in requests.iter_mut().enumerate() {
+        validate_request_data(request).map_err(|e| format!("{} (requests[{}])", e, index))?;
+        request.reset_derived_fields();
+    }
+    Ok(())
+}
+
+// Helper function to validate a request supplied by the client rather than captured
+fn validate_request_data(request: &RequestData) -> Result<(), String> {
+    if Method::from_bytes(request.method.as_bytes()).is_err() {
+        return Err(format!("Invalid HTTP method: {}", request.method));
+    }
+    if !request.path.starts_with('/') {
+        return Err("Path must start with /".into());
+    }
+    if let Some(encoded) = &request.body_base64 {
+        if base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .is_err()
+        {
+            return Err("body_base64 must be valid base64".into());
+        }
+    }
+    Ok(())
+}
+
+// Helper function to validate bucket name
+fn validate_bucket_name(name: &str, app_state: &AppState) -> Result<(), String> {
+    let reserved_names = &app_state.reserved_bucket_names;
+
+    // Check if empty
+    if name.is_empty() {
+        return Err("Bucket name cannot be empty".into());
+    }
+
+    // Check if reserved
+    if reserved_names.iter().any(|reserved| reserved == name) {
+        return Err(format!(
+            "Bucket name is reserved and cannot be used. Reserved names: {}",
+            reserved_names.join(", ")
+        ));
+    }
+
+    // Check length (reasonable limits)
+    if name.len() > app_state.bucket_name_max_len {
+        return Err(format!(
+            "Bucket name is too long (max {} characters)",
+            app_state.bucket_name_max_len
+        ));
+    }
+
+    // Prefix buckets (`team-a-*`) capture for every first path segment starting with the prefix
+    let (name, is_prefix) = match name.strip_suffix('*') {
+        Some(prefix) => (prefix, true),
+        None => (name, false),
+    };
+    if is_prefix && name.is_empty() {
+        return Err("Prefix bucket name needs at least one character before '*'".into());
+    }
+
+    if let Some(pattern) = &app_state.bucket_name_pattern {
+        if !pattern.is_match(name) {
+            return Err(format!(
+                "Bucket name must match the pattern {}",
+                pattern.as_str()
+            ));
+        }
+        return Ok(());
+    }
+
+    // Check for valid characters (alphanumeric, hyphens, underscores)
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(
+            "Bucket name can only contain alphanumeric characters, hyphens, and underscores".into(),
+        );
+    }
+
+    // Check that it doesn't start or end with hyphen/underscore (prefixes may end with one)
+    if name.starts_with('-')
+        || name.starts_with('_')
+        || (!is_prefix && (name.ends_with('-') || name.ends_with('_')))
+    {
+        return Err("Bucket name cannot start or end with hyphen or underscore".into());
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(req, query, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn export_bucket(
+    req: HttpRequest,
+    query: web::Query<ExportParams>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let base_url = request_base_url(&req);
+    let output = match query.format {
+        ExportFormat::Curl => bucket_ref
+            .requests
+            .iter()
+            .map(|request| {
+                export::curl_command(request, &base_url, app_state.password_header.as_str())
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    info!(count = bucket_ref.requests.len(), "Exported bucket");
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(output)
+}
+
+#[instrument(skip(req, payload, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn compare_bucket(
+    req: HttpRequest,
+    payload: web::Json<Vec<ExpectedRequest>>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    if payload.len() > MAX_REQUESTS_PER_BUCKET {
+        return error_response(
+            ErrorCode::BadRequest,
+            format!(
+                "Expected set cannot contain more than {} requests",
+                MAX_REQUESTS_PER_BUCKET
+            ),
+        );
+    }
+
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let diff = compare::compare_requests(bucket_name, &payload, &bucket_ref.requests);
+    info!(
+        matches = diff.matches,
+        "Compared bucket against expected requests"
+    );
+    HttpResponse::Ok().json(diff)
+}
+
+#[instrument(skip(req, query, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn diff_bucket_requests(
+    req: HttpRequest,
+    query: web::Query<DiffParams>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let (Some(a), Some(b)) = (
+        bucket_ref.requests.get(query.a),
+        bucket_ref.requests.get(query.b),
+    ) else {
+        warn!(a = query.a, b = query.b, "Diff index out of range");
+        return error_response(ErrorCode::BadRequest, "Request index out of range");
+    };
+
+    let diff = diff::diff_requests(a, b);
+    info!(identical = diff.identical, "Diffed requests");
+    HttpResponse::Ok().json(diff)
+}
+
+#[instrument(skip(req, query, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn extract_json_path(
+    req: HttpRequest,
+    query: web::Query<ExtractParams>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    let json_path = match JsonPath::parse(&query.path) {
+        Ok(json_path) => json_path,
+        Err(e) => {
+            warn!(error = %e, "Invalid JSONPath provided");
+            return error_response(ErrorCode::BadRequest, format!("Invalid JSONPath: {}", e));
+        }
+    };
+
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    // Bodies that aren't JSON are skipped
+    let mut matches = Vec::new();
+    for (index, request) in bucket_ref.requests.iter().enumerate() {
+        let Ok(body) = serde_json::from_str::<serde_json::Value>(&request.body) else {
+            continue;
+        };
+        matches.extend(
+            json_path
+                .query(&body)
+                .all()
+                .into_iter()
+                .map(|value| ExtractMatch {
+                    index,
+                    value: value.clone(),
+                }),
+        );
+    }
+
+    HttpResponse::Ok().json(matches)
+}
+
+#[instrument(skip(app_state, payload), fields(bucket_name = %path.as_str()))]
+pub async fn create_bucket(
+    req: HttpRequest,
+    path: web::Path<String>,
+    payload: web::Json<CreateBucketPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = path.as_ref();
+    // A bucket created now would be replaced by the restored one
+    if let Err(response) = ensure_loaded(&app_state) {
+        return response;
+    }
+    sync_bucket(&app_state, bucket_name).await;
+    let CreateBucketPayload {
+        password,
+        config,
+        mut requests,
+    } = payload.into_inner();
+    let idempotency_key = req
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|key| !key.is_empty());
+
+    if password.is_empty() {
+        warn!("Attempted to create bucket with empty password");
+        return error_response(ErrorCode::BadRequest, "Password cannot be empty");
+    }
+
+    if let Err(error_msg) = config.validate() {
+        warn!(error = %error_msg, "Attempted to create bucket with invalid configuration");
+        return error_response(ErrorCode::BadRequest, error_msg);
+    }
+
+    if let Err(error_msg) = prepare_seed_requests(&mut requests) {
+        warn!(error = %error_msg, "Attempted to create bucket with invalid seed requests");
+        return error_response(ErrorCode::BadRequest, error_msg);
+    }
+
+    if let Err(error_msg) = validate_replay_index(&config, requests.len()) {
+        warn!(error = %error_msg, "Attempted to create bucket with invalid replay_from_index");
+        return error_response(ErrorCode::BadRequest, error_msg);
+    }
+
+    // Validate bucket name
+    if let Err(error_msg) = validate_bucket_name(bucket_name, &app_state) {
+        warn!(
+            bucket_name = %bucket_name,
+            error = %error_msg,
+            "Attempted to create bucket with invalid name"
+        );
+        return error_response(ErrorCode::BadRequest, error_msg);
+    }
+
+    if let Some(key) = idempotency_key {
+        app_state
+            .idempotency_keys
+            .retain(|_, created| created.created_at.elapsed() < IDEMPOTENCY_KEY_TTL);
+        if let Some(created) = app_state.idempotency_keys.get(key) {
+            if created.bucket_name != *bucket_name {
+                warn!("Idempotency-Key reused for a different bucket");
+                return error_response(
+                    ErrorCode::UnprocessableEntity,
+                    "Idempotency-Key was already used for a different bucket",
+                );
+            }
+            // Repeat of the original request: answer as the original did while the bucket exists
+            let replayed = app_state
+                .buckets
+                .get(bucket_name)
+                .is_some_and(|bucket| verify_bucket_password(&bucket, &password));
+            if replayed {
+                info!("Repeated create with the same Idempotency-Key");
+                return HttpResponse::Ok().body("Bucket created");
+            }
+        }
+    }
+
+    if app_state.buckets.contains_key(bucket_name) {
+        warn!("Attempted to create a bucket that already exists");
+        return error_response(ErrorCode::Conflict, "Bucket already exists");
+    }
+
+    let Some(slot) = app_state.reserve_bucket_slot() else {
+        warn!(max_buckets = app_state.max_buckets, "Bucket limit reached");
+        return error_response(
+            ErrorCode::TooManyRequests,
+            "Maximum number of buckets reached",
+        );
+    };
+
+    let store_write = match app_state.buckets.entry(bucket_name.to_string()) {
+        dashmap::Entry::Occupied(_) => {
+            warn!("Bucket was created concurrently");
+            return error_response(ErrorCode::Conflict, "Bucket already exists");
+        }
+        dashmap::Entry::Vacant(entry) => {
+            let mut new_bucket = Bucket::new(password, config);
+            for request in requests {
+                new_bucket.push_request(request);
+            }
+            app_state
+                .total_bytes
+                .fetch_add(new_bucket.stored_bytes, AtomicOrdering::Relaxed);
+            let store_write = app_state
+                .shared_store
+                .as_ref()
+                .map(|store| store.create_bucket(bucket_name, &new_bucket));
+            entry.insert(new_bucket);
+            store_write
+        }
+    };
+    drop(slot);
+    finish_store_writes(store_write).await;
+    // Seed requests count towards GLOBAL_MAX_BYTES like captures
+    app_state.enforce_global_max_bytes();
+    if let Some(key) = idempotency_key {
+        app_state.idempotency_keys.insert(
+            key.to_string(),
+            IdempotentCreate {
+                bucket_name: bucket_name.to_string(),
+                created_at: Instant::now(),
+            },
+        );
+    }
+
+    info!("Successfully created new bucket");
+    HttpResponse::Ok().body("Bucket created")
+}
+
+#[instrument(skip(app_state, payload), fields(bucket_name = %path.as_str()))]
+pub async fn create_or_replace_bucket(
+    path: web::Path<String>,
+    payload: web::Json<ReplaceBucketPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = path.as_ref();
+    if let Err(response) = ensure_loaded(&app_state) {
+        return response;
+    }
+    sync_bucket(&app_state, bucket_name).await;
+    let ReplaceBucketPayload {
+        bucket:
+            CreateBucketPayload {
+                password,
+                config,
+                mut requests,
+            },
+        clear,
+    } = payload.into_inner();
+
+    if password.is_empty() {
+        warn!("Attempted to create bucket with empty password");
+        return error_response(ErrorCode::BadRequest, "Password cannot be empty");
+    }
+
+    if let Err(error_msg) = config.validate() {
+        warn!(error = %error_msg, "Attempted to create bucket with invalid configuration");
+        return error_response(ErrorCode::BadRequest, error_msg);
+    }
+
+    if let Err(error_msg) = prepare_seed_requests(&mut requests) {
+        warn!(error = %error_msg, "Attempted to create bucket with invalid seed requests");
+        return error_response(ErrorCode::BadRequest, error_msg);
+    }
+
+    if let Err(error_msg) = validate_bucket_name(bucket_name, &app_state) {
+        warn!(
+            bucket_name = %bucket_name,
+            error = %error_msg,
+            "Attempted to create bucket with invalid name"
+        );
+        return error_response(ErrorCode::BadRequest, error_msg);
+    }
+
+    // Existing bucket: the password must match to replace its configuration
+    if let Some(mut bucket_ref) = app_state.buckets.get_mut(bucket_name) {
+        if let Err(response) =
+            authorize_bucket(&bucket_ref, &password, app_state.clock.now_millis())
+        {
+            return response;
+        }
+        let kept = if clear { 0 } else { bucket_ref.requests.len() };
+        if let Err(error_msg) = validate_replay_index(&config, kept + requests.len()) {
+            warn!(error = %error_msg, "Attempted to set an invalid replay_from_index");
+            return error_response(ErrorCode::BadRequest, error_msg);
+        }
+        bucket_ref.config = config;
+        if clear {
+            let freed = bucket_ref.clear();
+            app_state
+                .total_bytes
+                .fetch_sub(freed, AtomicOrdering::Relaxed);
+        }
+        let mut store_writes = Vec::new();
+        if let Some(store) = &app_state.shared_store {
+            store_writes.push(store.update_bucket(bucket_name, &bucket_ref));
+            if clear {
+                store_writes.push(store.clear_requests(bucket_name));
+            }
+        }
+        // Seed requests are added after the old ones (if kept), like new captures
+        let stored_before = bucket_ref.stored_bytes;
+        for request in requests {
+            bucket_ref.push_request(request);
+            if let (Some(store), Some(request)) =
+                (&app_state.shared_store, bucket_ref.requests.back())
+            {
+                let keep = bucket_ref.requests.len();
+                store_writes.push(store.push_request(bucket_name, request, keep));
+            }
+        }
+        app_state.track_stored_bytes(stored_before, bucket_ref.stored_bytes);
+        drop(bucket_ref);
+        finish_store_writes(store_writes).await;
+        app_state.enforce_global_max_bytes();
+        info!(clear, "Replaced bucket configuration");
+        return HttpResponse::Ok().body("Bucket updated");
+    }
+
+    let Some(slot) = app_state.reserve_bucket_slot() else {
+        warn!(max_buckets = app_state.max_buckets, "Bucket limit reached");
+        return error_response(
+            ErrorCode::TooManyRequests,
+            "Maximum number of buckets reached",
+        );
+    };
+
+    let store_write = match app_state.buckets.entry(bucket_name.to_string()) {
+        dashmap::Entry::Occupied(_) => {
+            warn!("Bucket was created concurrently");
+            return error_response(ErrorCode::Conflict, "Bucket already exists");
+        }
+        dashmap::Entry::Vacant(entry) => {
+            if let Err(error_msg) = validate_replay_index(&config, requests.len()) {
+                warn!(error = %error_msg, "Attempted to create bucket with invalid replay_from_index");
+                return error_response(ErrorCode::BadRequest, error_msg);
+            }
+            let mut new_bucket = Bucket::new(password, config);
+            for request in requests {
+                new_bucket.push_request(request);
+            }
+            app_state
+                .total_bytes
+                .fetch_add(new_bucket.stored_bytes, AtomicOrdering::Relaxed);
+            let store_write = app_state
+                .shared_store
+                .as_ref()
+                .map(|store| store.create_bucket(bucket_name, &new_bucket));
+            entry.insert(new_bucket);
+            store_write
+        }
+    };
+    drop(slot);
+    finish_store_writes(store_write).await;
+    app_state.enforce_global_max_bytes();
+    info!("Successfully created new bucket");
+    HttpResponse::Ok().body("Bucket created")
+}
+
+// Helper function to hash and measure a request body chunk by chunk without keeping it,
+// stopping with `PayloadError::Overflow` as soon as it grows past `limit`
+async fn digest_payload(
+    mut payload: web::Payload,
+    limit: Option<usize>,
+) -> Result<(usize, String), PayloadError> {
+    let mut hasher = Sha256::new();
+    let mut length = 0;
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        length += chunk.len();
+        if limit.is_some_and(|limit| length > limit) {
+            return Err(PayloadError::Overflow);
+        }
+        hasher.update(&chunk);
+    }
+    Ok((length, hex::encode(hasher.finalize())))
+}
+
+// Body of a capture as read by `read_capture_body`
+struct CaptureBody {
+    bytes: web::Bytes,
+    // Size of the body as stored (decompressed when it was) and SHA-256 of the body as received
+    length: usize,
+    sha256: String,
+    content_encoding: Option<String>,
+    decompressed: bool,
+}
+
+// Helper function to read a capture's body as sent (the `Bytes` extractor would already decode
+// it) and decode it ourselves, so limits and parsing apply to what gets stored. Bodies the
+// bucket won't keep are only hashed as they stream in
+async fn read_capture_body(
+    req: &HttpRequest,
+    payload: web::Payload,
+    store_body: bool,
+    reject_body_over_bytes: Option<usize>,
+) -> Result<CaptureBody, HttpResponse> {
+    let (bytes, length, sha256) = if store_body {
+        match payload.to_bytes_limited(MAX_PAYLOAD_SIZE).await {
+            Ok(Ok(body)) => {
+                let (length, sha256) = (body.len(), hex::encode(Sha256::digest(&body)));
+                (body, length, sha256)
+            }
+            Ok(Err(e)) => {
+                warn!(error = %e, "Failed to read request body");
+                return Err(error_response(
+                    ErrorCode::BadRequest,
+                    "Failed to read request body",
+                ));
+            }
+            Err(_) => {
+                return Err(error_response(
+                    ErrorCode::PayloadTooLarge,
+                    "Request body too large",
+                ))
+            }
+        }
+    } else {
+        match digest_payload(payload, reject_body_over_bytes).await {
+            Ok((length, sha256)) => (web::Bytes::new(), length, sha256),
+            Err(PayloadError::Overflow) => {
+                warn!("Rejected request body over bucket limit");
+                return Err(error_response(
+                    ErrorCode::PayloadTooLarge,
+                    "Request body exceeds bucket limit",
+                ));
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to read request body");
+                return Err(error_response(
+                    ErrorCode::BadRequest,
+                    "Failed to read request body",
+                ));
+            }
+        }
+    };
+
+    let content_encoding = req
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let decoded = content_encoding
+        .as_deref()
+        .filter(|_| store_body)
+        .and_then(|encoding| decompress::decompress_body(encoding, &bytes, MAX_PAYLOAD_SIZE));
+    let body = match decoded {
+        Some(Ok(decoded)) => CaptureBody {
+            length: decoded.len(),
+            bytes: web::Bytes::from(decoded),
+            sha256,
+            content_encoding,
+            decompressed: true,
+        },
+        Some(Err(e)) => {
+            warn!(error = %e, "Failed to decompress request body, storing it as received");
+            CaptureBody {
+                bytes,
+                length,
+                sha256,
+                content_encoding,
+                decompressed: false,
+            }
+        }
+        None => CaptureBody {
+            bytes,
+            length,
+            sha256,
+            content_encoding,
+            decompressed: false,
+        },
+    };
+    Ok(body)
+}
+
+// Helper function to run a bucket's jq program over a JSON body, returning the transformed body.
+// Bodies that aren't JSON skip the program; bodies it fails or times out on are stored as received
+async fn transform_capture_body(
+    app_state: &AppState,
+    filter: &JqFilter,
+    body: &[u8],
+) -> Option<String> {
+    let input: serde_json::Value = serde_json::from_slice(body).ok()?;
+    match transform::run_jq_filter(filter, input, app_state.jq_filter_timeout).await {
+        Ok(transformed) => Some(transformed),
+        Err(e) => {
+            warn!(error = %e, "jq_filter failed, storing the body as received");
+            None
+        }
+    }
+}
+
+// Helper function to tell which filter (if any) keeps a capture from being stored, as the
+// counter it is tallied in
+fn filtered_capture_counter(
+    req: &HttpRequest,
+    app_state: &AppState,
+    config: &BucketConfig,
+) -> Option<BucketCounter> {
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    if config.ignores_user_agent(user_agent) {
+        info!(user_agent = ?user_agent, "Ignored capture from filtered user agent");
+        Some(BucketCounter::Ignored)
+    } else if !config.captures_content_type(content_type) {
+        info!(content_type = ?content_type, "Dropped capture with unwanted content type");
+        Some(BucketCounter::Dropped)
+    } else if let Some(rate) = config
+        .sample_rate
+        .filter(|&rate| !app_state.sample_rng.lock().unwrap().random_bool(rate))
+    {
+        info!(rate, "Sampled out capture");
+        Some(BucketCounter::SampledOut)
+    } else {
+        None
+    }
+}
+
+// Helper function to pick the status and body a capture is answered with, and whether the
+// capture itself should be echoed back instead
+#[allow(clippy::result_large_err)]
+fn select_capture_response(
+    req: &HttpRequest,
+    app_state: &AppState,
+    bucket: &Bucket,
+) -> Result<((StatusCode, String), bool), HttpResponse> {
+    let mut capture_response = (
+        app_state.capture_response_status,
+        app_state.capture_response_body.clone(),
+    );
+    // Stub mode answers with a stored request's body; mock headers still override it
+    if let Some(replayed) = bucket
+        .config
+        .replay_from_index
+        .and_then(|index| bucket.requests.get(index))
+    {
+        capture_response.1 = replayed.body.clone();
+    }
+    // A response the sender asked for explicitly wins over echoing the capture
+    let mut echo = bucket.config.echo.unwrap_or(false) && accepts_json(req);
+    if bucket.config.allow_mock_response.unwrap_or(false) {
+        match requested_mock_response(req) {
+            Ok(Some(mock)) => {
+                capture_response = mock;
+                echo = false;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!(error = %e, "Rejected request with invalid mock response");
+                return Err(error_response(ErrorCode::BadRequest, e));
+            }
+        }
+    }
+    Ok((capture_response, echo))
+}
+
+// Helper function to finish a capture once the bucket lock has been released: wait for its
+// shared store writes, keep within GLOBAL_MAX_BYTES and wake long-polling readers
+async fn finish_capture<'a>(
+    app_state: &'a AppState,
+    bucket_name: &str,
+    store_writes: Vec<StoreFuture<'a, ()>>,
+) {
+    finish_store_writes(store_writes).await;
+    app_state.enforce_global_max_bytes();
+    if let Some(notifier) = app_state.capture_notifiers.get(bucket_name) {
+        notifier.notify_waiters();
+    }
+}
+
+// Helper function to find the prefix bucket (e.g. `team-a-*`) with the longest prefix matching a path segment
+fn find_prefix_bucket(app_state: &AppState, segment: &str) -> Option<String> {
+    app_state
+        .buckets
+        .iter()
+        .filter_map(|entry| {
+            let prefix = entry.key().strip_suffix('*')?;
+            segment.starts_with(prefix).then(|| entry.key().clone())
+        })
+        .max_by_key(|name| name.len())
+}
+
+// Helper function to create a bucket on first capture when auto-creation is enabled
+fn auto_create_bucket<'a>(
+    app_state: &'a AppState,
+    bucket_name: &str,
+) -> Option<RefMut<'a, String, Bucket>> {
+    // Prefix buckets are only created explicitly
+    if !app_state.auto_create_buckets || bucket_name.ends_with('*') {
+        return None;
+    }
+
+    if let Err(error_msg) = validate_bucket_name(bucket_name, app_state) {
+        warn!(error = %error_msg, "Refusing to auto-create bucket with invalid name");
+        return None;
+    }
+
+    let Some(_slot) = app_state.reserve_bucket_slot() else {
+        warn!(
+            max_buckets = app_state.max_buckets,
+            "Bucket limit reached, refusing to auto-create bucket"
+        );
+        return None;
+    };
+
+    info!("Auto-creating bucket on first capture");
+    Some(
+        app_state
+            .buckets
+            .entry(bucket_name.to_string())
+            .or_insert_with(|| Bucket::new(String::new(), BucketConfig::default())),
+    )
+}
+
+#[instrument(skip(req, payload, app_state), fields(path = %req.path()))]
+pub async fn capture_request(
+    req: HttpRequest,
+    payload: web::Payload,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let started = Instant::now();
+    let path = req.path();
+    let bucket_name = match extract_bucket_name(path) {
+        Some(name) => name,
+        None => {
+            warn!("Request with invalid bucket path");
+            return error_response(ErrorCode::BadRequest, "Invalid bucket path.");
+        }
+    };
+    tracing::Span::current().record("bucket_name", bucket_name);
+    if let Err(response) = ensure_loaded(&app_state) {
+        return response;
+    }
+
+    sync_bucket(&app_state, bucket_name).await;
+
+    // Bodies the bucket won't keep are only hashed as they stream in, so check before reading
+    let (store_body, reject_body_over_bytes, jq_filter) = app_state
+        .buckets
+        .get(bucket_name)
+        .or_else(|| {
+            find_prefix_bucket(&app_state, bucket_name)
+                .and_then(|name| app_state.buckets.get(&name))
+        })
+        .map_or((true, None, None), |bucket_ref| {
+            (
+                bucket_ref.config.store_body.unwrap_or(true),
+                bucket_ref.config.reject_body_over_bytes,
+                bucket_ref.config.jq_filter.clone(),
+            )
+        });
+
+    let CaptureBody {
+        bytes: body,
+        length: body_length,
+        sha256: body_sha256,
+        content_encoding,
+        decompressed,
+    } = match read_capture_body(&req, payload, store_body, reject_body_over_bytes).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    // Transform before the bucket is locked, so a slow program doesn't hold up its shard
+    let transformed = match jq_filter.filter(|_| store_body) {
+        Some(filter) => transform_capture_body(&app_state, &filter, &body).await,
+        None => None,
+    };
+
+    let mut store_writes = Vec::new();
+    let (response_delay, failed, resolved_name, (response_status, response_body), echoed) = {
+        // Exact bucket first, then the longest matching prefix bucket, then auto-creation
+        let mut auto_created = false;
+        let bucket_ref = app_state
+            .buckets
+            .get_mut(bucket_name)
+            .or_else(|| {
+                find_prefix_bucket(&app_state, bucket_name)
+                    .and_then(|name| app_state.buckets.get_mut(&name))
+            })
+            .or_else(|| {
+                let bucket_ref = auto_create_bucket(&app_state, bucket_name);
+                auto_created = bucket_ref.is_some();
+                bucket_ref
+            });
+        let mut bucket_ref = match bucket_ref {
+            Some(bucket_ref) => bucket_ref,
+            None => {
+                warn!("Request for non-existent bucket");
+                return error_response(ErrorCode::NotFound, "Bucket not found");
+            }
+        };
+        let resolved_name = bucket_ref.key().clone();
+
+        if !bucket_ref.enabled {
+            info!("Ignored capture to paused bucket");
+            return match app_state.paused_capture_status {
+                Some(status) => HttpResponse::build(status).body("Capture paused"),
+                None => HttpResponse::build(app_state.capture_response_status)
+                    .body(app_state.capture_response_body.clone()),
+            };
+        }
+
+        if !bucket_ref.config.allows_method(req.method().as_str()) {
+            warn!(method = %req.method(), "Rejected request with disallowed method");
+            let allow = bucket_ref
+                .config
+                .allowed_methods
+                .iter()
+                .flatten()
+                .map(|m| m.trim().to_ascii_uppercase())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return HttpResponse::MethodNotAllowed()
+                .insert_header((header::ALLOW, allow))
+                .json(ErrorResponse {
+                    error: ErrorCode::MethodNotAllowed,
+                    message: "Method not allowed for this bucket".to_string(),
+                });
+        }
+
+        if let Some(required) = bucket_ref.config.require_origin.as_deref() {
+            let origin = request_origin(&req);
+            if origin.as_deref() != Some(required.trim_end_matches('/')) {
+                warn!(origin = ?origin, "Rejected request from disallowed origin");
+                return error_response(ErrorCode::Forbidden, "Origin not allowed");
+            }
+        }
+
+        let (capture_response, echo) = match select_capture_response(&req, &app_state, &bucket_ref)
+        {
+            Ok(selected) => selected,
+            Err(response) => return response,
+        };
+
+        // Answer filtered-out captures normally so senders don't retry, but don't store them
+        if let Some(counter) = filtered_capture_counter(&req, &app_state, &bucket_ref.config) {
+            *bucket_ref.counter_mut(counter) += 1;
+            let store_write = app_state
+                .shared_store
+                .as_ref()
+                .map(|store| store.increment_counter(&resolved_name, counter));
+            drop(bucket_ref);
+            finish_store_writes(store_write).await;
+            let (status, body) = capture_response;
+            return HttpResponse::build(status).body(body);
+        }
+
+        // A body larger than the byte quota could never be stored within it, and bodies that
+        // aren't stored are held to `reject_body_over_bytes` by their received size
+        let body_limit = bucket_ref
+            .config
+            .reject_body_over_bytes
+            .into_iter()
+            .chain(bucket_ref.config.max_total_bytes.filter(|_| store_body))
+            .min();
+        if let Some(limit) = body_limit {
+            if body_length > limit {
+                warn!(
+                    size = body_length,
+                    limit, "Rejected request body over bucket limit"
+                );
+                return error_response(
+                    ErrorCode::PayloadTooLarge,
+                    "Request body exceeds bucket limit",
+                );
+            }
+        }
+
+        // Expired requests go first so they don't count towards a full bucket
+        app_state.expire_requests(&mut bucket_ref);
+        if bucket_ref.config.overflow_policy.unwrap_or_default() == OverflowPolicy::Reject
+            && bucket_ref.requests.len() >= MAX_REQUESTS_PER_BUCKET
+        {
+            warn!("Rejected capture to full bucket");
+            return error_response(ErrorCode::InsufficientStorage, "Bucket is full");
+        }
+
+        let method = req.method().as_str();
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        let query_params = parse_query_params(req.query_string());
+        let (headers, headers_truncated) = capture_headers(
+            &req,
+            app_state.max_captured_headers,
+            app_state.max_header_value_bytes,
+        );
+        let parts = content_type
+            .filter(|_| store_body)
+            .and_then(multipart::multipart_boundary)
+            .map(|boundary| {
+                let inline_max_bytes = bucket_ref
+                    .config
+                    .multipart_inline_max_bytes
+                    .unwrap_or(DEFAULT_MULTIPART_INLINE_BYTES);
+                multipart::parse_multipart(&body, &boundary, inline_max_bytes)
+            });
+        let upload_bytes = parts
+            .as_ref()
+            .map(|parts| parts.iter().map(|part| part.size).sum());
+        let grpc_web = content_type.is_some_and(grpc_web::is_grpc_web) && store_body;
+        let grpc_trailers = grpc_web.then(|| grpc_web::parse_trailers(&body)).flatten();
+        let body_base64 = grpc_web.then(|| base64::engine::general_purpose::STANDARD.encode(&body));
+        // The payload is only kept once, so binary bodies aren't counted twice against the quotas
+        let mut body = if body_base64.is_some() {
+            String::new()
+        } else {
+            String::from_utf8_lossy(&body).into_owned()
+        };
+        let raw_body = transformed.map(|transformed| std::mem::replace(&mut body, transformed));
+        if bucket_ref.config.minify_json.unwrap_or(false) {
+            if let Some(minified) = minify_json(&body) {
+                body = minified;
+            }
+        }
+        let failed = bucket_ref
+            .config
+            .fail_first_n
+            .is_some_and(|n| bucket_ref.capture_count < n as u64);
+        let host = request_host(&req);
+        let scheme = req.connection_info().scheme().to_string();
+        // The bucket was resolved from the full path, so cutting it only affects what is stored
+        let mut stored_path = path.to_string();
+        let path_truncated =
+            truncate_at_char_boundary(&mut stored_path, app_state.max_captured_path_bytes);
+        let full_url = host.as_ref().map(|host| {
+            let query = req.query_string();
+            let separator = if query.is_empty() { "" } else { "?" };
+            format!("{}://{}{}{}{}", scheme, host, stored_path, separator, query)
+        });
+
+        let segments = extract_path_segments(&stored_path);
+        let channel = segments.first().cloned();
+
+        let request_data = RequestData {
+            path: stored_path,
+            method: method.to_string(),
+            query_params,
+            raw_query: req.query_string().to_string(),
+            headers,
+            body,
+            raw_body,
+            body_base64,
+            grpc_trailers,
+            timestamp: app_state.clock.now_millis(),
+            http_version: format!("{:?}", req.version()),
+            connection_info: Some(ConnectionInfo {
+                http_version: format!("{:?}", req.version()),
+                is_tls: scheme == "https",
+            }),
+            segments,
+            parts,
+            upload_bytes,
+            failed,
+            body_sha256,
+            body_length,
+            headers_truncated,
+            tags: Vec::new(),
+            full_url,
+            host,
+            scheme,
+            content_encoding,
+            decompressed,
+            path_truncated,
+            channel,
+            // Matches the id `push_request` assigns, so published and shared copies carry it too
+            seq: bucket_ref.last_id + 1,
+            client_cert_subject: app_state
+                .capture_client_cert
+                .then(|| client_cert::client_cert_subject(&req))
+                .flatten(),
+            processing_micros: started.elapsed().as_micros() as u64,
+        };
+
+        info!(method = %method, failed, "Captured request");
+        app_state.broadcast_capture(&resolved_name, &request_data);
+        if let Some(store) = &app_state.shared_store {
+            if auto_created {
+                store_writes.push(store.create_bucket(&resolved_name, &bucket_ref));
+            }
+        }
+
+        let echoed = echo.then(|| request_data.clone());
+        let stored_before = bucket_ref.stored_bytes;
+        bucket_ref.push_request(request_data);
+        bucket_ref.capture_count += 1;
+        app_state.track_stored_bytes(stored_before, bucket_ref.stored_bytes);
+        // Pushed after storing it here, so the shared copy keeps the requests that survived
+        if let (Some(store), Some(request)) = (&app_state.shared_store, bucket_ref.requests.back())
+        {
+            let keep = bucket_ref.requests.len();
+            store_writes.push(store.push_request(&resolved_name, request, keep));
+            store_writes.push(store.increment_counter(&resolved_name, BucketCounter::Captured));
+        }
+        (
+            bucket_ref.config.response_delay(),
+            failed,
+            resolved_name,
+            capture_response,
+            echoed,
+        )
+    };
+
+    finish_capture(&app_state, &resolved_name, store_writes).await;
+
+    // Delay the response after the bucket lock has been released
+    if let Some(delay) = response_delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    if failed {
+        return HttpResponse::ServiceUnavailable().body("Simulated failure");
+    }
+    if let Some(request) = echoed {
+        return HttpResponse::build(response_status).json(request);
+    }
+
+    HttpResponse::build(response_status).body(response_body)
+}
+
+#[instrument(skip(req, app_state, query), fields(bucket_name = req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_bucket_requests(
+    req: HttpRequest,
+    query: web::Query<PaginationParams>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    let path_regex = match query.path_regex.as_deref() {
+        Some(pattern) => match app_state.regex_cache.get_or_compile(pattern) {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                warn!(error = %e, "Invalid path_regex provided");
+                return error_response(ErrorCode::BadRequest, format!("Invalid path_regex: {}", e));
+            }
+        },
+        None => None,
+    };
+
+    if query.header_value.is_some() && query.header_name.is_none() {
+        warn!("header_value provided without header_name");
+        return error_response(ErrorCode::BadRequest, "header_value requires header_name");
+    }
+
+    let token = query.token.as_deref();
+    let matches_filters = |r: &RequestData| {
+        query
+            .after_timestamp
+            .is_none_or(|after| r.timestamp > after)
+            && path_regex.as_ref().is_none_or(|re| re.is_match(&r.path))
+            && query.tag.as_ref().is_none_or(|tag| r.tags.contains(tag))
+            && query
+                .channel
+                .as_ref()
+                .is_none_or(|channel| r.channel.as_ref() == Some(channel))
+            && query
+                .header_name
+                .as_ref()
+                .is_none_or(|name| has_header(r, name, query.header_value.as_deref()))
+    };
+
+    if let Some(wait_ms) = query.wait_ms {
+        // Authenticate before registering a notifier so unknown buckets don't get one
+        if let Err(response) = authenticate_bucket_read(&req, &app_state, bucket_name, token) {
+            return response;
+        }
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_millis(wait_ms.min(MAX_LONG_POLL_MS));
+        let notifier = app_state
+            .capture_notifiers
+            .entry(bucket_name.to_string())
+            .or_default()
+            .clone();
+        loop {
+            // Register for wake-ups before checking so a capture in between isn't missed
+            let notified = notifier.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            match authenticate_bucket_read(&req, &app_state, bucket_name, token) {
+                Ok(bucket_ref) if bucket_ref.requests.iter().any(matches_filters) => break,
+                Ok(_) => {}
+                Err(response) => return response,
+            }
+            // Captures on other instances don't wake this one, so re-check the shared store
+            // periodically
+            let wake_at = if app_state.shared_store.is_some() {
+                deadline.min(tokio::time::Instant::now() + SHARED_STORE_POLL_INTERVAL)
+            } else {
+                deadline
+            };
+            if tokio::time::timeout_at(wake_at, notified).await.is_err() {
+                if wake_at == deadline {
+                    break;
+                }
+                sync_bucket(&app_state, bucket_name).await;
+            }
+        }
+    }
+
+    let bucket_ref = match authenticate_bucket_read(&req, &app_state, bucket_name, token) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let etag = bucket_etag(&bucket_ref, req.query_string());
+    if etag_matches(&req, &etag) {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish();
+    }
+
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query
+        .page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    // Without `sort_by`, `sort_dir=desc` lists the newest requests first
+    let newest_first = query.sort_by.is_none() && query.sort_dir == SortDirection::Desc;
+    let has_filters = query.after_timestamp.is_some()
+        || path_regex.is_some()
+        || query.tag.is_some()
+        || query.channel.is_some()
+        || query.header_name.is_some();
+
+    let (total, requests) = if query.sort_by.is_none() && !has_filters {
+        // Unfiltered pages are sliced straight out of the bucket, counting from the tail when
+        // newest first, so only the requests on the page are visited
+        let total = bucket_ref.requests.len();
+        let (start, end) = page_bounds(total, page, page_size);
+        let requests = if newest_first {
+            let page = bucket_ref.requests.range(total - end..total - start);
+            page.rev().cloned().collect()
+        } else {
+            bucket_ref.requests.range(start..end).cloned().collect()
+        };
+        (total, requests)
+    } else {
+        let mut filtered: Vec<&RequestData> = bucket_ref
+            .requests
+            .iter()
+            .filter(|r| matches_filters(r))
+            .collect();
+
+        if let Some(sort_by) = query.sort_by.as_deref() {
+            sort_by_body_field(&mut filtered, sort_by, query.sort_dir);
+        } else if newest_first {
+            filtered.reverse();
+        }
+
+        let (start, end) = page_bounds(filtered.len(), page, page_size);
+        let requests = filtered[start..end].iter().map(|r| (*r).clone()).collect();
+        (filtered.len(), requests)
+    };
+    let total_pages = total.div_ceil(page_size);
+
+    let response = PaginatedResponse {
+        requests,
+        total,
+        page,
+        page_size,
+        total_pages,
+        watermark: bucket_ref.watermark().encode(),
+        dropped_count: bucket_ref.dropped_count,
+        sampled_out_count: bucket_ref.sampled_out_count,
+        ignored_count: bucket_ref.ignored_count,
+    };
+
+    let indent = wants_pretty_json(&req, query.pretty);
+    let mut builder = HttpResponse::Ok();
+    builder.insert_header((header::ETAG, etag));
+    if query.fields == Some(RequestFields::Meta) {
+        let mut value = serde_json::to_value(&response).unwrap_or_default();
+        if let Some(requests) = value["requests"].as_array_mut() {
+            requests.iter_mut().for_each(strip_body);
+        }
+        return json_response(&mut builder, &value, indent);
+    }
+    if query.pretty {
+        let mut value = serde_json::to_value(&response).unwrap_or_default();
+        if let Some(requests) = value["requests"].as_array_mut() {
+            requests.iter_mut().for_each(parse_json_body);
+        }
+        return json_response(&mut builder, &value, indent);
+    }
+    json_response(&mut builder, &response, indent)
+}
+
+#[instrument(skip(req, app_state, query), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_requests_since(
+    req: HttpRequest,
+    query: web::Query<SinceParams>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    let watermark = match query.watermark.as_deref() {
+        Some(value) => match Watermark::decode(value) {
+            Some(watermark) => Some(watermark),
+            None => {
+                warn!("Malformed watermark provided");
+                return error_response(ErrorCode::BadRequest, "Invalid watermark");
+            }
+        },
+        None => None,
+    };
+
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let current = bucket_ref.watermark();
+    let new_requests = match watermark {
+        // Without a watermark, start from the beginning of the current generation
+        None => Some(bucket_ref.requests.iter()),
+        Some(watermark) if watermark.generation == current.generation => {
+            bucket_ref.requests_since(watermark.last_id)
+        }
+        Some(_) => None,
+    };
+
+    let response = match new_requests {
+        Some(requests) => SinceResponse {
+            requests: requests.cloned().collect(),
+            watermark: current.encode(),
+            resync: false,
+        },
+        None => {
+            info!("Watermark is stale, client must resync");
+            SinceResponse {
+                requests: Vec::new(),
+                watermark: current.encode(),
+                resync: true,
+            }
+        }
+    };
+
+    HttpResponse::Ok().json(response)
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_bucket_config(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    HttpResponse::Ok().json(BucketSettings {
+        config: bucket_ref.config.clone(),
+        enabled: bucket_ref.enabled,
+        password_expires_at: bucket_ref.password_expires_at,
+    })
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_latest_request(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    match bucket_ref.requests.back() {
+        Some(request) => HttpResponse::Ok().json(request),
+        None => HttpResponse::NoContent().finish(),
+    }
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_nth_from_last_request(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let n: usize = match req.match_info().query("n").parse() {
+        Ok(n) => n,
+        Err(_) => return error_response(ErrorCode::BadRequest, "Invalid request offset"),
+    };
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    // 0 is the latest request, 1 the one before it, and so on
+    let index = bucket_ref
+        .requests
+        .len()
+        .checked_sub(n)
+        .and_then(|i| i.checked_sub(1));
+    match index.and_then(|index| bucket_ref.requests.get(index)) {
+        Some(request) => HttpResponse::Ok().json(request),
+        None => error_response(ErrorCode::NotFound, "Request not found"),
+    }
+}
+
+// Helper function to read one bucket's latest `limit` requests for a batch fetch
+fn batch_bucket_result(
+    app_state: &AppState,
+    bucket_name: &str,
+    password: &str,
+    limit: usize,
+) -> BatchBucketResult {
+    let Some(mut bucket_ref) = app_state.buckets.get_mut(bucket_name) else {
+        return missing_bucket_error(app_state, password).into();
+    };
+    if let Err(error) = check_bucket_access(&bucket_ref, password, app_state.clock.now_millis()) {
+        return error.into();
+    }
+    app_state.expire_requests(&mut bucket_ref);
+    let total = bucket_ref.requests.len();
+    let requests = bucket_ref
+        .requests
+        .iter()
+        .skip(total.saturating_sub(limit))
+        .cloned()
+        .collect();
+    BatchBucketResult::Requests { requests, total }
+}
+
+#[instrument(skip(req, payload, app_state))]
+pub async fn get_requests_batch(
+    req: HttpRequest,
+    payload: web::Json<BatchRequestsPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(response) = ensure_loaded(&app_state) {
+        return response;
+    }
+    let BatchRequestsPayload {
+        buckets,
+        passwords,
+        limit,
+    } = payload.into_inner();
+    if buckets.len() > MAX_BATCH_BUCKETS {
+        return error_response(
+            ErrorCode::BadRequest,
+            format!("At most {} buckets per batch", MAX_BATCH_BUCKETS),
+        );
+    }
+    let limit = limit.unwrap_or(DEFAULT_BATCH_LIMIT).min(MAX_PAGE_SIZE);
+    let shared_password = get_request_password(&req, &app_state).ok();
+
+    // Each bucket is authorized on its own, so one bad password doesn't fail the whole batch
+    let mut results = BTreeMap::new();
+    for bucket_name in buckets {
+        sync_bucket(&app_state, &bucket_name).await;
+        let password = passwords
+            .get(&bucket_name)
+            .map(String::as_str)
+            .or(shared_password.as_deref());
+        let result = match password {
+            Some(password) => batch_bucket_result(&app_state, &bucket_name, password, limit),
+            None => ErrorResponse::new(ErrorCode::Unauthorized, "Password required").into(),
+        };
+        results.insert(bucket_name, result);
+    }
+
+    info!(count = results.len(), "Served batch of bucket requests");
+    HttpResponse::Ok().json(results)
+}
+
+#[instrument(skip(req, query, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_requests_by_method(
+    req: HttpRequest,
+    query: web::Query<MethodGroupParams>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let mut groups: BTreeMap<&str, Vec<&RequestData>> = BTreeMap::new();
+    for request in &bucket_ref.requests {
+        groups.entry(&request.method).or_default().push(request);
+    }
+    // Each group is paginated on its own, oldest request first
+    if query.page.is_some() || query.page_size.is_some() {
+        let page = query.page.unwrap_or(1).max(1);
+        let page_size = query
+            .page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        for requests in groups.values_mut() {
+            let (start, end) = page_bounds(requests.len(), page, page_size);
+            requests.truncate(end);
+            requests.drain(..start);
+        }
+    }
+    HttpResponse::Ok().json(groups)
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn subscribe_bucket(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    // Drop subscriptions that stopped polling
+    app_state
+        .subscriptions
+        .retain(|_, subscription| subscription.last_polled.elapsed() < SUBSCRIPTION_TTL);
+
+    let subscriber_id = hex::encode(rand::rng().random::<[u8; 16]>());
+    app_state.subscriptions.insert(
+        subscriber_id.clone(),
+        Subscription {
+            bucket_name: bucket_name.to_string(),
+            cursor: bucket_ref.watermark(),
+            last_polled: Instant::now(),
+        },
+    );
+
+    info!("Created subscription");
+    HttpResponse::Ok().json(SubscribeResponse { subscriber_id })
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn poll_subscription(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let subscriber_id = req.match_info().get("subscriber_id").unwrap_or_default();
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let mut subscription = match app_state.subscriptions.get_mut(subscriber_id) {
+        Some(subscription)
+            if subscription.bucket_name == bucket_name
+                && subscription.last_polled.elapsed() < SUBSCRIPTION_TTL =>
+        {
+            subscription
+        }
+        _ => {
+            warn!("Poll for unknown or expired subscription");
+            return error_response(ErrorCode::NotFound, "Subscription not found");
+        }
+    };
+
+    let current = bucket_ref.watermark();
+    let new_requests = if subscription.cursor.generation == current.generation {
+        bucket_ref.requests_since(subscription.cursor.last_id)
+    } else {
+        None
+    };
+    let response = match new_requests {
+        Some(requests) => PollResponse {
+            requests: requests.cloned().collect(),
+            resync: false,
+        },
+        None => PollResponse {
+            requests: bucket_ref.requests.iter().cloned().collect(),
+            resync: true,
+        },
+    };
+
+    subscription.cursor = current;
+    subscription.last_polled = Instant::now();
+    HttpResponse::Ok().json(response)
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_embed_token(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    HttpResponse::Ok().json(EmbedTokenResponse {
+        token: embed_token(bucket_name, &bucket_ref.password),
+    })
+}
+
+#[instrument(skip(req, query, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_embed_snippet(
+    req: HttpRequest,
+    query: web::Query<EmbedParams>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    // The snippet runs on other sites, so it needs the server's public address rather than
+    // whatever Host header this request arrived with
+    let Some(base_url) = &app_state.public_base_url else {
+        warn!("Embed snippet requested but PUBLIC_BASE_URL is not set");
+        return error_response(ErrorCode::NotFound, "Embedding is disabled");
+    };
+    if let Err(response) = authenticate_bucket_token(&app_state, bucket_name, &query.token) {
+        return response;
+    }
+
+    // `<` is escaped so a value can't close the inline script with `</script>`
+    let to_json = |value: &str| {
+        serde_json::to_string(value)
+            .unwrap_or_default()
+            .replace('<', "\\u003c")
+    };
+    let snippet = EMBED_TEMPLATE
+        .replace("__BUCKET_HTML__", &escape_html(bucket_name))
+        .replace("__BUCKET_JSON__", &to_json(bucket_name))
+        .replace("__TOKEN_JSON__", &to_json(&query.token))
+        .replace("__BASE_URL_JSON__", &to_json(base_url));
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(snippet)
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn delete_bucket(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    if let Err(response) = ensure_loaded(&app_state) {
+        return response;
+    }
+    let password = match get_request_password(&req, &app_state) {
+        Ok(password) => password,
+        Err(response) => return response,
+    };
+
+    // The password is checked under the same lock that removes the bucket, so a bucket
+    // re-created in between is never removed without its own password
+    let now = app_state.clock.now_millis();
+    let mut refused = None;
+    let removed = app_state.buckets.remove_if(bucket_name, |_, bucket| {
+        match check_bucket_access(bucket, &password, now) {
+            Ok(()) => true,
+            Err(e) => {
+                refused = Some(e);
+                false
+            }
+        }
+    });
+    match (removed, refused) {
+        (Some((_, bucket)), _) => {
+            app_state
+                .total_bytes
+                .fetch_sub(bucket.stored_bytes, AtomicOrdering::Relaxed);
+        }
+        (None, Some(e)) => {
+            error!("Unauthorized deletion attempt");
+            return e.into_response();
+        }
+        (None, None) => return missing_bucket_response(&app_state, &password),
+    }
+    let store_write = app_state
+        .shared_store
+        .as_ref()
+        .map(|store| store.delete_bucket(bucket_name));
+    finish_store_writes(store_write).await;
+    app_state
+        .subscriptions
+        .retain(|_, subscription| subscription.bucket_name != bucket_name);
+    app_state.capture_notifiers.remove(bucket_name);
+    info!("Successfully deleted bucket");
+    HttpResponse::Ok().body("Bucket deleted")
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn clear_bucket_requests(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    let mut bucket_ref = match authenticate_bucket_mut(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let freed = bucket_ref.clear();
+    app_state
+        .total_bytes
+        .fetch_sub(freed, AtomicOrdering::Relaxed);
+    drop(bucket_ref);
+    let store_write = app_state
+        .shared_store
+        .as_ref()
+        .map(|store| store.clear_requests(bucket_name));
+    finish_store_writes(store_write).await;
+    info!("Successfully cleared requests from bucket");
+    HttpResponse::Ok().body("Bucket requests cleared")
+}
+
+#[instrument(skip(req, payload, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn set_password_expiry(
+    req: HttpRequest,
+    payload: web::Json<PasswordExpiryPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    let mut bucket_ref = match authenticate_bucket_mut(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    bucket_ref.password_expires_at = payload.expires_at;
+    let store_write = app_state
+        .shared_store
+        .as_ref()
+        .map(|store| store.update_bucket(bucket_name, &bucket_ref));
+    drop(bucket_ref);
+    finish_store_writes(store_write).await;
+    info!(expires_at = ?payload.expires_at, "Updated bucket password expiry");
+    HttpResponse::Ok().body("Password expiry updated")
+}
+
+// Helper function to pause or resume capture on the bucket named in the request path
+async fn set_capture_enabled(
+    req: &HttpRequest,
+    app_state: &AppState,
+    enabled: bool,
+) -> HttpResponse {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(app_state, bucket_name).await;
+
+    let mut bucket_ref = match authenticate_bucket_mut(req, app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    bucket_ref.enabled = enabled;
+    let store_write = app_state
+        .shared_store
+        .as_ref()
+        .map(|store| store.update_bucket(bucket_name, &bucket_ref));
+    drop(bucket_ref);
+    finish_store_writes(store_write).await;
+    if enabled {
+        info!("Resumed capture");
+        HttpResponse::Ok().body("Capture resumed")
+    } else {
+        info!("Paused capture");
+        HttpResponse::Ok().body("Capture paused")
+    }
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn pause_bucket(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    set_capture_enabled(&req, &app_state, false).await
+}
+
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn resume_bucket(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    set_capture_enabled(&req, &app_state, true).await
+}
+
+#[instrument(skip(req, payload, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn rotate_bucket_password(
+    req: HttpRequest,
+    payload: web::Json<RotatePasswordPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let password = match get_request_password(&req, &app_state) {
+        Ok(pwd) => pwd,
+        Err(response) => return response,
+    };
+    let new_password = payload.into_inner().new_password;
+
+    if new_password.is_empty() {
+        warn!("Attempted to rotate to an empty password");
+        return error_response(ErrorCode::BadRequest, "Password cannot be empty");
+    }
+
+    let store_write = match app_state.buckets.get_mut(bucket_name) {
+        Some(mut bucket_ref) => {
+            // Rotation only checks the current password so expired buckets can recover
+            if !verify_bucket_password(&bucket_ref, &password) {
+                warn!("Invalid password provided for rotation");
+                return error_response(ErrorCode::Unauthorized, "Invalid password");
+            }
+            if verify_bucket_password(&bucket_ref, &new_password) {
+                warn!("Attempted to rotate to the same password");
+                return error_response(
+                    ErrorCode::BadRequest,
+                    "New password must differ from the current password",
+                );
+            }
+
+            bucket_ref.password = new_password;
+            bucket_ref.password_expires_at = None;
+            app_state
+                .shared_store
+                .as_ref()
+                .map(|store| store.update_bucket(bucket_name, &bucket_ref))
+        }
+        None => return missing_bucket_response(&app_state, &password),
+    };
+    finish_store_writes(store_write).await;
+    info!("Successfully rotated bucket password");
+    HttpResponse::Ok().body("Password rotated")
+}
+
+#[instrument(skip(req, payload, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn tag_request(
+    req: HttpRequest,
+    payload: web::Json<TagPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let index: usize = match req.match_info().query("index").parse() {
+        Ok(index) => index,
+        Err(_) => return error_response(ErrorCode::BadRequest, "Invalid request index"),
+    };
+
+    let mut tags: Vec<String> = payload.tags.iter().map(|t| t.trim().to_string()).collect();
+    if tags.iter().any(String::is_empty) {
+        return error_response(ErrorCode::BadRequest, "Tags must not be empty");
+    }
+    tags.sort();
+    tags.dedup();
+
+    let mut bucket_ref = match authenticate_bucket_mut(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+    let Some(request) = bucket_ref.requests.get_mut(index) else {
+        return error_response(ErrorCode::NotFound, "Request not found");
+    };
+    request.tags = tags;
+    let tags = request.tags.clone();
+    let store_writes = app_state.shared_store.as_ref().map(|store| {
+        [
+            store.update_request(bucket_name, request),
+            store.increment_counter(bucket_name, BucketCounter::Revision),
+        ]
+    });
+    bucket_ref.revision += 1;
+    drop(bucket_ref);
+    finish_store_writes(store_writes.into_iter().flatten()).await;
+
+    info!(index, "Tagged request");
+    HttpResponse::Ok().json(TagPayload { tags })
+}
+
+#[instrument(skip(req, payload, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn inject_request(
+    req: HttpRequest,
+    payload: web::Json<serde_json::Value>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+
+    let mut payload = payload.into_inner();
+    // Injected requests without a timestamp are stamped like a capture arriving now
+    if let Some(fields) = payload.as_object_mut() {
+        fields
+            .entry("timestamp")
+            .or_insert_with(|| app_state.clock.now_millis().into());
+    }
+    let mut request: RequestData = match serde_json::from_value(payload) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!(error = %e, "Invalid injected request");
+            return error_response(ErrorCode::BadRequest, format!("Invalid request: {}", e));
+        }
+    };
+    if let Err(error_msg) = validate_request_data(&request) {
+        warn!(error = %error_msg, "Invalid injected request");
+        return error_response(ErrorCode::BadRequest, error_msg);
+    }
+    request.reset_derived_fields();
+
+    let mut bucket_ref = match authenticate_bucket_mut(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+    if bucket_ref.config.overflow_policy.unwrap_or_default() == OverflowPolicy::Reject
+        && bucket_ref.requests.len() >= MAX_REQUESTS_PER_BUCKET
+    {
+        warn!("Rejected injected request to full bucket");
+        return error_response(ErrorCode::InsufficientStorage, "Bucket is full");
+    }
+    // Injected bodies are held to the same limits as captured ones, counting every copy stored
+    let body_limit = bucket_ref
+        .config
+        .reject_body_over_bytes
+        .into_iter()
+        .chain(bucket_ref.config.max_total_bytes)
+        .min();
+    if let Some(limit) = body_limit {
+        if request.stored_size() > limit {
+            warn!(
+                size = request.stored_size(),
+                limit, "Rejected injected request body over bucket limit"
+            );
+            return error_response(
+                ErrorCode::PayloadTooLarge,
+                "Request body exceeds bucket limit",
+            );
+        }
+    }
+
+    request.seq = bucket_ref.last_id + 1;
+    app_state.broadcast_capture(bucket_name, &request);
+    let stored_before = bucket_ref.stored_bytes;
+    bucket_ref.push_request(request.clone());
+    app_state.track_stored_bytes(stored_before, bucket_ref.stored_bytes);
+    let store_write = app_state
+        .shared_store
+        .as_ref()
+        .map(|store| store.push_request(bucket_name, &request, bucket_ref.requests.len()));
+    drop(bucket_ref);
+    finish_store_writes(store_write).await;
+    app_state.enforce_global_max_bytes();
+
+    if let Some(notifier) = app_state.capture_notifiers.get(bucket_name) {
+        notifier.notify_waiters();
+    }
+
+    info!(method = %request.method, "Injected request");
+    HttpResponse::Ok().json(request)
+}
+
+// Helper function to resolve a replay target to the address to connect to. Hosts resolving to a
+// non-public address (loopback, private networks, cloud metadata, ...) are refused unless they are
+// listed in REPLAY_ALLOWED_HOSTS, so replays can't reach the server's own network
+async fn resolve_replay_target(
+    app_state: &AppState,
+    url: &Uri,
+) -> Result<SocketAddr, HttpResponse> {
+    let host = url
+        .host()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let default_port = if url.scheme_str() == Some("https") {
+        443
+    } else {
+        80
+    };
+    let port = url.port_u16().unwrap_or(default_port);
+    let addresses: Vec<SocketAddr> = match tokio::net::lookup_host((host, port)).await {
+        Ok(addresses) => addresses.collect(),
+        Err(e) => {
+            warn!(error = %e, "Failed to resolve replay target");
+            return Err(error_response(
+                ErrorCode::BadGateway,
+                format!("Replay failed: could not resolve {}", host),
+            ));
+        }
+    };
+    let allowed = app_state
+        .replay_allowed_hosts
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(host));
+    // Every address must be public, as the connection could otherwise use a private one
+    if !allowed && addresses.iter().any(|address| !is_public_ip(address.ip())) {
+        warn!(host, "Refused to replay to a non-public address");
+        return Err(error_response(
+            ErrorCode::Forbidden,
+            "target_url resolves to a non-public address",
+        ));
+    }
+    addresses.into_iter().next().ok_or_else(|| {
+        error_response(
+            ErrorCode::BadGateway,
+            format!("Replay failed: could not resolve {}", host),
+        )
+    })
+}
+
+// Helper function to tell whether an address is reachable on the public internet
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // "This network" (0.0.0.0/8) and reserved (240.0.0.0/4)
+                || a == 0
+                || a >= 240
+                // Shared address space (100.64.0.0/10)
+                || (a == 100 && (b & 0xc0) == 64)
+                // Benchmarking (198.18.0.0/15)
+                || (a == 198 && (b & 0xfe) == 18)
+                // IETF protocol assignments (192.0.0.0/24)
+                || (a == 192 && b == 0 && c == 0))
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            // IPv4-mapped and NAT64 (64:ff9b::/96) addresses reach the embedded IPv4 address
+            if let Some(ipv4) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ipv4));
+            }
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [.., a, b, c, d] = ip.octets();
+                return is_public_ip(IpAddr::from([a, b, c, d]));
+            }
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local (fc00::/7) and link-local (fe80::/10)
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                // Documentation (2001:db8::/32)
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+        }
+    }
+}
+
+#[instrument(skip(req, payload, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn replay_request(
+    req: HttpRequest,
+    payload: web::Json<ReplayPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
+    let index: usize = match req.match_info().query("index").parse() {
+        Ok(index) => index,
+        Err(_) => return error_response(ErrorCode::BadRequest, "Invalid request index"),
+    };
+
+    let target_url = match payload.target_url.parse::<Uri>() {
+        Ok(url) if is_http_url(&url) => url,
+        _ => {
+            warn!("Invalid replay target URL");
+            return error_response(ErrorCode::BadRequest, "target_url must be an http(s) URL");
+        }
+    };
+
+    // Copy the stored request so the bucket isn't locked during the upstream call
+    let stored = {
+        let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+            Ok(bucket_ref) => bucket_ref,
+            Err(response) => return response,
+        };
+        match bucket_ref.requests.get(index) {
+            Some(request) => request.clone(),
+            None => return error_response(ErrorCode::NotFound, "Request not found"),
+        }
+    };
 
-// Helper function to extract and validate password from request
-fn get_password_from_header(req: &HttpRequest) -> Result<&str, HttpResponse> {
-    match req.headers().get(PASSWORD_HEADER) {
-        Some(p) => Ok(p.to_str().unwrap_or("")),
-        None => {
-            warn!("Password header missing");
-            Err(HttpResponse::Unauthorized().body("Password required"))
+    // Captures from `store_body=false` buckets only kept the body's size, so they can't be sent again
+    if stored.body.is_empty()
+        && stored.raw_body.is_none()
+        && stored.body_base64.is_none()
+        && stored.body_length > 0
+    {
+        warn!(
+            index,
+            "Attempted to replay a request without its stored body"
+        );
+        return error_response(
+            ErrorCode::Conflict,
+            "Request body was not stored, so it can't be replayed",
+        );
+    }
+
+    let method = match Method::from_bytes(stored.method.as_bytes()) {
+        Ok(method) => method,
+        Err(_) => {
+            return error_response(
+                ErrorCode::BadRequest,
+                "Stored request has an invalid method",
+            )
+        }
+    };
+    let address = match resolve_replay_target(&app_state, &target_url).await {
+        Ok(address) => address,
+        Err(response) => return response,
+    };
+    // Connect to the address that was checked rather than resolving the host again
+    let mut upstream = awc::Client::builder()
+        .timeout(REPLAY_TIMEOUT)
+        .disable_redirects()
+        .finish()
+        .request(method, target_url)
+        .address(address);
+    for (name, value) in &stored.headers {
+        // The body was stored decompressed, so it no longer matches its Content-Encoding
+        let stale_encoding = stored.decompressed && name.eq_ignore_ascii_case("content-encoding");
+        let skipped = REPLAY_SKIPPED_HEADERS.contains(&name.as_str())
+            || name.eq_ignore_ascii_case(app_state.password_header.as_str());
+        if !skipped && !stale_encoding {
+            upstream = upstream.append_header((name.as_str(), value.as_str()));
         }
     }
-}
 
-// Helper function to verify password against bucket
-fn verify_bucket_password(bucket: &Bucket, password: &str) -> bool {
-    bucket.password.as_bytes().ct_eq(password.as_bytes()).into()
+    // gRPC-Web bodies are only held losslessly in `body_base64`
+    let body = match stored.body_base64.as_deref().and_then(|encoded| {
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .ok()
+    }) {
+        Some(bytes) => bytes,
+        None => stored.body.into_bytes(),
+    };
+    let mut response = match upstream.send_body(body).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!(error = %e, "Replay request failed");
+            return error_response(ErrorCode::BadGateway, format!("Replay failed: {}", e));
+        }
+    };
+    let status = response.status().as_u16();
+    let body = match response.body().limit(MAX_PAYLOAD_SIZE).await {
+        Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+        Err(e) => {
+            warn!(error = %e, "Failed to read replay response");
+            return error_response(ErrorCode::BadGateway, format!("Replay failed: {}", e));
+        }
+    };
+
+    info!(index, status, "Replayed request");
+    HttpResponse::Ok().json(ReplayResponse { status, body })
 }
 
-// Helper function to parse query parameters
-fn parse_query_params(query_string: &str) -> HashMap<String, String> {
-    if query_string.is_empty() {
-        return HashMap::new();
-    }
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_bucket_paths(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
 
-    query_string
-        .split('&')
-        .filter_map(|pair| {
-            let mut parts = pair.splitn(2, '=');
-            match (parts.next(), parts.next()) {
-                (Some(key), Some(value)) if !key.is_empty() => {
-                    Some((key.to_string(), value.to_string()))
-                }
-                (Some(key), None) if !key.is_empty() => Some((key.to_string(), String::new())),
-                _ => None,
-            }
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for request in &bucket_ref.requests {
+        *counts.entry(request.path.as_str()).or_default() += 1;
+    }
+    let mut paths: Vec<PathCount> = counts
+        .into_iter()
+        .map(|(path, count)| PathCount {
+            path: path.to_string(),
+            count,
         })
-        .collect()
-}
+        .collect();
+    paths.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.path.cmp(&b.path)));
 
-#[derive(Deserialize, Serialize)]
-pub struct CreateBucketPayload {
-    pub password: String,
+    HttpResponse::Ok().json(paths)
 }
 
-#[derive(Deserialize)]
-pub struct PaginationParams {
-    pub page: Option<usize>,
-    pub page_size: Option<usize>,
-}
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn get_bucket_headers(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
 
-#[derive(Serialize)]
-pub struct PaginatedResponse {
-    pub requests: Vec<RequestData>,
-    pub total: usize,
-    pub page: usize,
-    pub page_size: usize,
-    pub total_pages: usize,
-}
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
+
+    let mut values: BTreeMap<String, (HashSet<&str>, &str)> = BTreeMap::new();
+    for request in &bucket_ref.requests {
+        for (name, value) in &request.headers {
+            let (distinct, _) = values
+                .entry(name.to_lowercase())
+                .or_insert_with(|| (HashSet::new(), value.as_str()));
+            distinct.insert(value.as_str());
+        }
+    }
+    let headers: BTreeMap<String, HeaderSummary> = values
+        .into_iter()
+        .map(|(name, (distinct, sample))| {
+            let summary = HeaderSummary {
+                distinct_values: distinct.len(),
+                sample: sample.to_string(),
+            };
+            (name, summary)
+        })
+        .collect();
 
-// Helper function to check if bucket name is reserved
-fn is_reserved_bucket_name(name: &str) -> bool {
-    RESERVED_BUCKET_NAMES.contains(&name)
+    HttpResponse::Ok().json(headers)
 }
 
-// Helper function to validate bucket name
-fn validate_bucket_name(name: &str) -> Result<(), &'static str> {
-    // Check if empty
-    if name.is_empty() {
-        return Err("Bucket name cannot be empty");
-    }
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn dump_bucket(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
 
-    // Check if reserved
-    if is_reserved_bucket_name(name) {
-        return Err("Bucket name is reserved and cannot be used. Reserved names: api, ui");
-    }
+    let bucket_ref = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref,
+        Err(response) => return response,
+    };
 
-    // Check length (reasonable limits)
-    if name.len() > 100 {
-        return Err("Bucket name is too long (max 100 characters)");
-    }
+    let export = BucketExport {
+        requests: bucket_ref.requests.iter().cloned().collect(),
+        config: bucket_ref.config.clone(),
+    };
+    info!(count = export.requests.len(), "Dumped bucket");
+    HttpResponse::Ok().json(export)
+}
 
-    // Check for valid characters (alphanumeric, hyphens, underscores)
-    if !name
-        .chars()
-        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
-    {
-        return Err(
-            "Bucket name can only contain alphanumeric characters, hyphens, and underscores",
-        );
-    }
+#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
+pub async fn download_bucket(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
+    sync_bucket(&app_state, bucket_name).await;
 
-    // Check that it doesn't start or end with hyphen/underscore
-    if name.starts_with('-') || name.starts_with('_') || name.ends_with('-') || name.ends_with('_')
-    {
-        return Err("Bucket name cannot start or end with hyphen or underscore");
-    }
+    // Copy the requests out so the bucket isn't locked while the client downloads
+    let requests: Vec<RequestData> = match authenticate_bucket(&req, &app_state, bucket_name) {
+        Ok(bucket_ref) => bucket_ref.requests.iter().cloned().collect(),
+        Err(response) => return response,
+    };
+    info!(count = requests.len(), "Downloading bucket");
 
-    Ok(())
+    // Serialize one request per chunk instead of building the whole array up front
+    let items = requests.into_iter().enumerate().map(|(index, request)| {
+        let mut chunk = if index == 0 {
+            Vec::new()
+        } else {
+            b",".to_vec()
+        };
+        serde_json::to_writer(&mut chunk, &request).map(|_| web::Bytes::from(chunk))
+    });
+    let body = stream::iter(
+        iter::once(Ok(web::Bytes::from_static(b"[")))
+            .chain(items)
+            .chain(iter::once(Ok(web::Bytes::from_static(b"]")))),
+    );
+
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header(header::ContentDisposition {
+            disposition: header::DispositionType::Attachment,
+            parameters: vec![header::DispositionParam::Filename(format!(
+                "{}.json",
+                bucket_name
+            ))],
+        })
+        .streaming(body)
 }
 
 #[instrument(skip(app_state, payload), fields(bucket_name = %path.as_str()))]
-pub async fn create_bucket(
+pub async fn import_bucket(
     path: web::Path<String>,
-    payload: web::Json<CreateBucketPayload>,
+    payload: web::Json<ImportBucketPayload>,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
     let bucket_name = path.as_ref();
-    let password = payload.into_inner().password;
+    if let Err(response) = ensure_loaded(&app_state) {
+        return response;
+    }
+    sync_bucket(&app_state, bucket_name).await;
+    let ImportBucketPayload { password, bucket } = payload.into_inner();
 
     if password.is_empty() {
-        warn!("Attempted to create bucket with empty password");
-        return HttpResponse::BadRequest().body("Password cannot be empty");
+        warn!("Attempted to import bucket with empty password");
+        return error_response(ErrorCode::BadRequest, "Password cannot be empty");
     }
 
-    // Validate bucket name
-    if let Err(error_msg) = validate_bucket_name(bucket_name) {
+    if let Err(error_msg) = validate_bucket_name(bucket_name, &app_state) {
         warn!(
             bucket_name = %bucket_name,
             error = %error_msg,
-            "Attempted to create bucket with invalid name"
+            "Attempted to import bucket with invalid name"
         );
-        return HttpResponse::BadRequest().body(error_msg);
+        return error_response(ErrorCode::BadRequest, error_msg);
     }
 
-    if app_state.buckets.contains_key(bucket_name) {
-        warn!("Attempted to create a bucket that already exists");
-        return HttpResponse::Conflict().body("Bucket already exists");
+    if let Err(error_msg) = bucket
+        .config
+        .validate()
+        .and_then(|_| validate_replay_index(&bucket.config, bucket.requests.len()))
+    {
+        warn!(error = %error_msg, "Attempted to import bucket with invalid configuration");
+        return error_response(ErrorCode::BadRequest, error_msg);
     }
 
-    let new_bucket = Bucket {
-        password,
-        requests: Vec::new(),
+    let Some(slot) = app_state.reserve_bucket_slot() else {
+        warn!(max_buckets = app_state.max_buckets, "Bucket limit reached");
+        return error_response(
+            ErrorCode::TooManyRequests,
+            "Maximum number of buckets reached",
+        );
     };
-    app_state
-        .buckets
-        .insert(bucket_name.to_string(), new_bucket);
 
-    info!("Successfully created new bucket");
-    HttpResponse::Ok().body("Bucket created")
+    let store_write = match app_state.buckets.entry(bucket_name.to_string()) {
+        dashmap::Entry::Occupied(_) => {
+            warn!("Attempted to import a bucket that already exists");
+            return error_response(ErrorCode::Conflict, "Bucket already exists");
+        }
+        dashmap::Entry::Vacant(entry) => {
+            let mut new_bucket = Bucket::new(password, bucket.config);
+            // Keep only the most recent requests if the dump exceeds the per-bucket limits
+            for request in bucket.requests {
+                new_bucket.push_request(request);
+            }
+
+            info!(
+                count = new_bucket.requests.len(),
+                "Successfully imported bucket"
+            );
+            app_state
+                .total_bytes
+                .fetch_add(new_bucket.stored_bytes, AtomicOrdering::Relaxed);
+            let store_write = app_state
+                .shared_store
+                .as_ref()
+                .map(|store| store.create_bucket(bucket_name, &new_bucket));
+            entry.insert(new_bucket);
+            store_write
+        }
+    };
+    drop(slot);
+    finish_store_writes(store_write).await;
+    app_state.enforce_global_max_bytes();
+    HttpResponse::Ok().body("Bucket imported")
 }
 
-#[instrument(skip(req, body, app_state), fields(path = %req.path()))]
-pub async fn capture_request(
+#[instrument(skip(req, app_state, query))]
+pub async fn get_storage_report(
     req: HttpRequest,
-    body: web::Bytes,
+    query: web::Query<PrettyParams>,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
-    let path = req.path();
-    let bucket_name = match extract_bucket_name(path) {
-        Some(name) => name,
-        None => {
-            warn!("Request with invalid bucket path");
-            return HttpResponse::BadRequest().body("Invalid bucket path.");
-        }
+    if let Err(response) = authenticate_admin(&req, &app_state) {
+        return response;
+    }
+    let report = StorageReport {
+        total_bytes: app_state.total_bytes.load(AtomicOrdering::Relaxed),
+        buckets: app_state.buckets.len(),
     };
-    tracing::Span::current().record("bucket_name", &bucket_name);
+    json_response(
+        &mut HttpResponse::Ok(),
+        &report,
+        wants_pretty_json(&req, query.pretty),
+    )
+}
 
-    if let Some(mut bucket_ref) = app_state.buckets.get_mut(bucket_name) {
-        let method = req.method().as_str();
-        let query_params = parse_query_params(req.query_string());
-        let headers: HashMap<String, String> = req
-            .headers()
-            .iter()
-            .map(|(k, v)| (k.as_str().to_string(), v.to_str().unwrap_or("").to_string()))
-            .collect();
-        let body = String::from_utf8_lossy(&body).into_owned();
+#[instrument(skip(req, app_state, query))]
+pub async fn search_requests(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    query: web::Query<SearchParams>,
+) -> impl Responder {
+    if let Err(response) = authenticate_admin(&req, &app_state) {
+        return response;
+    }
+    if query.q.is_empty() {
+        return error_response(ErrorCode::BadRequest, "Search query must not be empty");
+    }
 
-        let request_data = RequestData {
-            path: path.to_string(),
-            method: method.to_string(),
-            query_params,
-            headers,
-            body,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as i64,
-        };
+    let needle = query.q.to_lowercase();
+    let matches = |request: &RequestData| {
+        request.path.to_lowercase().contains(&needle)
+            || request.body.to_lowercase().contains(&needle)
+            || request.headers.iter().any(|(name, value)| {
+                name.to_lowercase().contains(&needle) || value.to_lowercase().contains(&needle)
+            })
+    };
 
-        info!(method = %method, "Captured request");
+    // Search buckets in name order so capped results are stable
+    let mut names: Vec<String> = app_state
+        .buckets
+        .iter()
+        .map(|entry| entry.key().clone())
+        .collect();
+    names.sort();
 
-        // Limit the number of requests per bucket
-        if bucket_ref.requests.len() >= MAX_REQUESTS_PER_BUCKET {
-            bucket_ref.requests.remove(0); // Remove oldest request
+    let mut results = Vec::new();
+    for name in names {
+        // Buckets past the cap couldn't add results, so they aren't synced or locked either
+        if results.len() == MAX_SEARCH_RESULTS {
+            break;
+        }
+        // Search the same requests a listing of the bucket would return
+        sync_bucket(&app_state, &name).await;
+        let Some(mut bucket) = app_state.buckets.get_mut(&name) else {
+            continue;
+        };
+        app_state.expire_requests(&mut bucket);
+        let bucket = bucket.downgrade();
+        for (index, request) in bucket.requests.iter().enumerate() {
+            if results.len() == MAX_SEARCH_RESULTS {
+                break;
+            }
+            if matches(request) {
+                results.push(SearchResult {
+                    bucket: name.clone(),
+                    index,
+                    request: request.clone(),
+                });
+            }
         }
-
-        bucket_ref.requests.push(request_data);
-        HttpResponse::Ok().body("Request captured")
-    } else {
-        warn!("Request for non-existent bucket");
-        HttpResponse::NotFound().body("Bucket not found")
     }
+
+    info!(results = results.len(), "Searched all buckets");
+    HttpResponse::Ok().json(results)
 }
 
-#[instrument(skip(req, app_state, query), fields(bucket_name = req.match_info().get("bucket_name").unwrap_or("unknown")))]
-pub async fn get_bucket_requests(
+#[instrument(skip(req, app_state))]
+pub async fn stream_all_captures(
     req: HttpRequest,
-    query: web::Query<PaginationParams>,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
-    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
-    let password = match get_password_from_header(&req) {
-        Ok(pwd) => pwd,
-        Err(response) => return response,
-    };
+    if let Err(response) = authenticate_admin(&req, &app_state) {
+        return response;
+    }
 
-    match app_state.buckets.get(bucket_name) {
-        Some(bucket_ref) => {
-            if !verify_bucket_password(&bucket_ref, password) {
-                warn!("Invalid password provided for bucket");
-                return HttpResponse::Unauthorized().body("Invalid password");
+    info!("Admin capture stream opened");
+    let events = stream::unfold(
+        app_state.capture_stream.subscribe(),
+        |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        let frame = match serde_json::to_string(&event) {
+                            Ok(json) => format!("data: {}\n\n", json),
+                            Err(e) => {
+                                warn!(error = %e, "Failed to serialize capture event");
+                                continue;
+                            }
+                        };
+                        return Some((
+                            Ok::<_, actix_web::Error>(web::Bytes::from(frame)),
+                            receiver,
+                        ));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(missed, "Admin capture stream fell behind, dropped captures");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
             }
+        },
+    );
 
-            let total = bucket_ref.requests.len();
-            let page = query.page.unwrap_or(1).max(1);
-            let page_size = query
-                .page_size
-                .unwrap_or(DEFAULT_PAGE_SIZE)
-                .min(MAX_PAGE_SIZE)
-                .max(1);
-            let total_pages = (total + page_size - 1) / page_size;
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events)
+}
 
-            let start = (page - 1) * page_size;
-            let end = (start + page_size).min(total);
+#[instrument(skip(req, app_state))]
+pub async fn reset_all(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    if let Err(response) = authenticate_admin(&req, &app_state) {
+        return response;
+    }
 
-            let requests = if start < total {
-                bucket_ref.requests[start..end].to_vec()
-            } else {
-                Vec::new()
-            };
+    let mut removed_names = Vec::new();
+    app_state.buckets.retain(|name, bucket| {
+        removed_names.push(name.clone());
+        app_state
+            .total_bytes
+            .fetch_sub(bucket.stored_bytes, AtomicOrdering::Relaxed);
+        false
+    });
+    app_state.subscriptions.clear();
+    app_state.capture_notifiers.clear();
+    app_state.idempotency_keys.clear();
+    // Removed from the shared store too, or the next request would load them back
+    if let Some(store) = &app_state.shared_store {
+        finish_store_writes(removed_names.iter().map(|name| store.delete_bucket(name))).await;
+    }
+    let removed_buckets = removed_names.len();
 
-            let response = PaginatedResponse {
-                requests,
-                total,
-                page,
-                page_size,
-                total_pages,
-            };
+    info!(removed_buckets, "Reset all buckets");
+    HttpResponse::Ok().json(ResetResponse { removed_buckets })
+}
 
-            HttpResponse::Ok().json(response)
-        }
-        None => {
-            warn!("Request for non-existent bucket");
-            HttpResponse::NotFound().body("Bucket not found")
-        }
+#[instrument(skip(req, app_state))]
+pub async fn purge_empty_buckets(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(response) = authenticate_admin(&req, &app_state) {
+        return response;
     }
-}
 
-#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
-pub async fn delete_bucket(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
-    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
-    let password = match get_password_from_header(&req) {
-        Ok(pwd) => pwd,
-        Err(response) => return response,
-    };
+    // Refresh the empty buckets first, so ones that captured on another instance are kept
+    if app_state.shared_store.is_some() {
+        let empty: Vec<String> = app_state
+            .buckets
+            .iter()
+            .filter(|entry| entry.requests.is_empty())
+            .map(|entry| entry.key().clone())
+            .collect();
+        for name in &empty {
+            sync_bucket(&app_state, name).await;
+        }
+    }
 
-    // First check authentication
-    if let Some((_, bucket)) = app_state.buckets.remove(bucket_name) {
-        if verify_bucket_password(&bucket, password) {
-            info!("Successfully deleted bucket");
-            HttpResponse::Ok().body("Bucket deleted")
-        } else {
-            // Re-insert the bucket since password was wrong
-            app_state.buckets.insert(bucket_name.to_string(), bucket);
-            error!("Invalid password provided for deletion");
-            HttpResponse::Unauthorized().body("Invalid password")
+    let mut removed_buckets = Vec::new();
+    // Empty buckets hold no stored bytes, so `total_bytes` is unaffected
+    app_state.buckets.retain(|name, bucket| {
+        let empty = bucket.requests.is_empty();
+        if empty {
+            removed_buckets.push(name.clone());
         }
-    } else {
-        error!("Bucket not found for deletion");
-        HttpResponse::NotFound().body("Bucket not found")
+        !empty
+    });
+    removed_buckets.sort();
+    app_state
+        .subscriptions
+        .retain(|_, subscription| !removed_buckets.contains(&subscription.bucket_name));
+    for name in &removed_buckets {
+        app_state.capture_notifiers.remove(name);
     }
+    if let Some(store) = &app_state.shared_store {
+        finish_store_writes(removed_buckets.iter().map(|name| store.delete_bucket(name))).await;
+    }
+
+    info!(removed = removed_buckets.len(), "Purged empty buckets");
+    HttpResponse::Ok().json(PurgeResponse { removed_buckets })
 }
 
-#[instrument(skip(req, app_state), fields(bucket_name = %req.match_info().get("bucket_name").unwrap_or("unknown")))]
-pub async fn clear_bucket_requests(
+pub async fn list_buckets(
     req: HttpRequest,
+    query: web::Query<ListBucketsParams>,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
-    let bucket_name = req.match_info().get("bucket_name").unwrap_or_default();
-    let password = match get_password_from_header(&req) {
-        Ok(pwd) => pwd,
-        Err(response) => return response,
-    };
-
-    match app_state.buckets.get_mut(bucket_name) {
-        Some(mut bucket_ref) => {
-            if verify_bucket_password(&bucket_ref, password) {
-                bucket_ref.requests.clear();
-                info!("Successfully cleared requests from bucket");
-                HttpResponse::Ok().body("Bucket requests cleared")
-            } else {
-                error!("Invalid password provided");
-                HttpResponse::Unauthorized().body("Invalid password")
-            }
-        }
-        None => {
-            error!("Bucket not found");
-            HttpResponse::NotFound().body("Bucket not found")
+    if let Err(response) = ensure_loaded(&app_state) {
+        return response;
+    }
+    // Listing names would defeat hiding which buckets exist, so it becomes an admin call
+    if app_state.hide_bucket_existence {
+        if let Err(response) = authenticate_admin(&req, &app_state) {
+            return response;
         }
     }
-}
-
-pub async fn list_buckets(app_state: web::Data<AppState>) -> impl Responder {
+    let prefix = query.prefix.as_deref().unwrap_or_default();
     let names: Vec<String> = app_state
         .buckets
         .iter()
+        .filter(|entry| entry.key().starts_with(prefix))
         .map(|entry| entry.key().clone())
         .collect();
     info!(count = names.len(), "Served list of buckets");
-    HttpResponse::Ok().json(names)
+    json_response(
+        &mut HttpResponse::Ok(),
+        &names,
+        wants_pretty_json(&req, query.pretty),
+    )
+}
+
+pub async fn get_openapi_spec() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(OPENAPI_SPEC.replace("__VERSION__", env!("CARGO_PKG_VERSION")))
 }
 
 pub async fn get_version() -> impl Responder {
     HttpResponse::Ok().body(env!("CARGO_PKG_VERSION"))
 }
 
+/// Register an API route for `method` (see [`ApiResource`]).
+pub fn api_resource<F, Args>(path: &str, method: Method, handler: F) -> ApiResource
+where
+    F: Handler<Args>,
+    Args: FromRequest + 'static,
+    F::Output: Responder + 'static,
+{
+    ApiResource::new(path).to(method, handler)
+}
+
+/// API resource that answers OPTIONS with an `Allow` header listing the permitted methods
+/// (GET routes also accept HEAD) and any other method with 405.
+pub struct ApiResource {
+    resource: Resource,
+    methods: Vec<Method>,
+}
+
+impl ApiResource {
+    pub fn new(path: &str) -> Self {
+        ApiResource {
+            resource: web::resource(path),
+            methods: Vec::new(),
+        }
+    }
+
+    /// Add a handler for `method` on this resource.
+    pub fn to<F, Args>(mut self, method: Method, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.resource = self
+            .resource
+            .route(web::route().method(method.clone()).to(handler.clone()));
+        if method == Method::GET {
+            self.resource = self.resource.route(web::head().to(handler));
+            self.methods.push(Method::GET);
+            self.methods.push(Method::HEAD);
+        } else {
+            self.methods.push(method);
+        }
+        self
+    }
+}
+
+impl HttpServiceFactory for ApiResource {
+    fn register(self, config: &mut AppService) {
+        let allow = self
+            .methods
+            .iter()
+            .map(Method::as_str)
+            .chain(["OPTIONS"])
+            .collect::<Vec<_>>()
+            .join(", ");
+        let allow_response = move |status: StatusCode| {
+            let allow = allow.clone();
+            move || {
+                let allow = allow.clone();
+                async move {
+                    HttpResponse::build(status)
+                        .insert_header((header::ALLOW, allow))
+                        .finish()
+                }
+            }
+        };
+
+        self.resource
+            .route(
+                web::route()
+                    .method(Method::OPTIONS)
+                    .to(allow_response(StatusCode::NO_CONTENT)),
+            )
+            .default_service(web::to(allow_response(StatusCode::METHOD_NOT_ALLOWED)))
+            .register(config)
+    }
+}
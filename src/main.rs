@@ -1,38 +1,86 @@
 use actix_cors::Cors;
 use actix_files::Files;
+use actix_web::http::Method;
 use actix_web::{web, App, HttpResponse, HttpServer};
 use request_catcher::{
-    capture_request, clear_bucket_requests, create_bucket, delete_bucket, get_bucket_requests,
-    list_buckets, get_version, AppState,
+    api_resource, capture_request, clear_bucket_requests, compare_bucket, create_bucket,
+    create_or_replace_bucket, delete_bucket, diff_bucket_requests, download_bucket, dump_bucket,
+    export_bucket, extract_json_path, get_bucket_config, get_bucket_headers, get_bucket_paths,
+    get_bucket_requests, get_embed_snippet, get_embed_token, get_latest_request,
+    get_nth_from_last_request, get_openapi_spec, get_requests_batch, get_requests_by_method,
+    get_requests_since, get_storage_report, get_version, import_bucket, inject_request,
+    json_error_handler, list_buckets, pause_bucket, poll_subscription, purge_empty_buckets,
+    replay_request, reset_all, restore_snapshot, resume_bucket, rotate_bucket_password,
+    search_requests, set_password_expiry, stream_all_captures, subscribe_bucket, tag_request,
+    write_snapshot, AppState, ListenAddress, LogFormat, PersistConfig, StaticFiles,
+    MAX_PAYLOAD_SIZE,
 };
 use std::env;
-use tracing::info;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
-const MAX_PAYLOAD_SIZE: usize = 10 * 1024 * 1024; // 10MB
-
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Initialize tracing subscriber for structured logging
     // Log level can be set with the RUST_LOG environment variable (e.g., RUST_LOG=info,request_catcher=debug)
+    // Output format can be set with the LOG_FORMAT environment variable (text or json)
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    let log_format = LogFormat::from_env_value(env::var("LOG_FORMAT").ok().as_deref())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    match log_format {
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(env_filter).init(),
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_current_span(true)
+            .with_env_filter(env_filter)
+            .init(),
+    }
 
-    let app_state = web::Data::new(AppState {
-        buckets: Default::default(),
-    });
+    let app_state = web::Data::new(
+        AppState::from_env()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+    );
+
+    // Buckets are restored in the background once the server is up; until then requests get 503
+    let persist_config = PersistConfig::from_env()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    if persist_config.is_some() {
+        app_state.loading.store(true, Ordering::Release);
+    }
 
-    // Get host and port from environment variables, with defaults for development
-    let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let port = env::var("PORT").unwrap_or_else(|_| "9090".to_string());
-    let address = format!("{}:{}", host, port);
+    // Listen on LISTEN_UDS if set, otherwise on HOST and PORT, with defaults for development
+    let listen_address = ListenAddress::from_env_values(
+        env::var("LISTEN_UDS").ok().as_deref(),
+        env::var("HOST").ok().as_deref(),
+        env::var("PORT").ok().as_deref(),
+    );
+    match &listen_address {
+        ListenAddress::Tcp(address) => info!("Server starting on http://{}", address),
+        ListenAddress::Unix(path) => info!("Server starting on unix:{}", path.display()),
+    }
 
-    info!("Server starting on http://{}", address);
+    // The UI is served from STATIC_DIR, relative to the working directory unless absolute
+    let static_files = StaticFiles::from_env_values(
+        env::var("STATIC_DIR").ok().as_deref(),
+        env::var("INDEX_FILE").ok().as_deref(),
+    );
+    if !static_files.dir.is_dir() {
+        warn!(
+            dir = %static_files.dir.display(),
+            "Static directory not found, the UI will not be served (set STATIC_DIR)"
+        );
+    }
+
+    let snapshot_state = app_state.clone();
 
     let server = HttpServer::new(move || {
+        let ui_files = Files::new("/", &static_files.dir).index_file(&static_files.index_file);
         App::new()
             .app_data(app_state.clone())
             .app_data(web::PayloadConfig::new(MAX_PAYLOAD_SIZE))
+            .app_data(web::JsonConfig::default().error_handler(json_error_handler))
             .service(
                 web::scope("/api")
                     .wrap(
@@ -41,22 +89,177 @@ async fn main() -> std::io::Result<()> {
                             .allow_any_method()
                             .allow_any_header(),
                     )
-                    .route("/buckets", web::get().to(list_buckets))
-                    .route("/version", web::get().to(get_version))
-                    .route(
+                    .service(api_resource("/buckets", Method::GET, list_buckets))
+                    .service(api_resource("/version", Method::GET, get_version))
+                    .service(api_resource("/openapi.json", Method::GET, get_openapi_spec))
+                    .service(api_resource(
                         "/clear/{bucket_name}",
-                        web::post().to(clear_bucket_requests),
+                        Method::POST,
+                        clear_bucket_requests,
+                    ))
+                    .service(api_resource(
+                        "/delete/{bucket_name}",
+                        Method::DELETE,
+                        delete_bucket,
+                    ))
+                    .service(
+                        api_resource("/create/{bucket_name}", Method::POST, create_bucket)
+                            .to(Method::PUT, create_or_replace_bucket),
                     )
-                    .route("/delete/{bucket_name}", web::delete().to(delete_bucket))
-                    .route("/create/{bucket_name}", web::post().to(create_bucket))
-                    .route(
+                    .service(api_resource(
+                        "/config/{bucket_name}",
+                        Method::GET,
+                        get_bucket_config,
+                    ))
+                    .service(api_resource(
+                        "/pause/{bucket_name}",
+                        Method::POST,
+                        pause_bucket,
+                    ))
+                    .service(api_resource(
+                        "/resume/{bucket_name}",
+                        Method::POST,
+                        resume_bucket,
+                    ))
+                    .service(api_resource(
+                        "/download/{bucket_name}",
+                        Method::GET,
+                        download_bucket,
+                    ))
+                    .service(api_resource(
+                        "/requests/{bucket_name}/by-method",
+                        Method::GET,
+                        get_requests_by_method,
+                    ))
+                    .service(api_resource(
+                        "/requests/{bucket_name}/last/{n}",
+                        Method::GET,
+                        get_nth_from_last_request,
+                    ))
+                    .service(api_resource(
+                        "/extract/{bucket_name}",
+                        Method::GET,
+                        extract_json_path,
+                    ))
+                    .service(api_resource(
+                        "/headers/{bucket_name}",
+                        Method::GET,
+                        get_bucket_headers,
+                    ))
+                    .service(api_resource(
+                        "/diff/{bucket_name}",
+                        Method::GET,
+                        diff_bucket_requests,
+                    ))
+                    .service(api_resource("/admin/reset", Method::POST, reset_all))
+                    .service(api_resource(
+                        "/admin/purge-empty",
+                        Method::POST,
+                        purge_empty_buckets,
+                    ))
+                    .service(api_resource(
+                        "/admin/storage",
+                        Method::GET,
+                        get_storage_report,
+                    ))
+                    .service(api_resource("/admin/search", Method::GET, search_requests))
+                    .service(api_resource(
+                        "/admin/stream",
+                        Method::GET,
+                        stream_all_captures,
+                    ))
+                    .service(api_resource(
+                        "/requests/{bucket_name}/{index}/tag",
+                        Method::POST,
+                        tag_request,
+                    ))
+                    .service(api_resource(
+                        "/paths/{bucket_name}",
+                        Method::GET,
+                        get_bucket_paths,
+                    ))
+                    .service(api_resource(
+                        "/requests-batch",
+                        Method::POST,
+                        get_requests_batch,
+                    ))
+                    .service(api_resource(
+                        "/inject/{bucket_name}",
+                        Method::POST,
+                        inject_request,
+                    ))
+                    .service(api_resource(
+                        "/replay/{bucket_name}/{index}",
+                        Method::POST,
+                        replay_request,
+                    ))
+                    .service(api_resource(
+                        "/subscribe/{bucket_name}",
+                        Method::POST,
+                        subscribe_bucket,
+                    ))
+                    .service(api_resource(
+                        "/poll/{bucket_name}/{subscriber_id}",
+                        Method::GET,
+                        poll_subscription,
+                    ))
+                    .service(api_resource(
+                        "/compare/{bucket_name}",
+                        Method::POST,
+                        compare_bucket,
+                    ))
+                    .service(api_resource(
+                        "/export/{bucket_name}",
+                        Method::GET,
+                        export_bucket,
+                    ))
+                    .service(api_resource(
+                        "/embed-token/{bucket_name}",
+                        Method::GET,
+                        get_embed_token,
+                    ))
+                    .service(api_resource(
+                        "/embed/{bucket_name}",
+                        Method::GET,
+                        get_embed_snippet,
+                    ))
+                    .service(api_resource(
+                        "/latest/{bucket_name}",
+                        Method::GET,
+                        get_latest_request,
+                    ))
+                    .service(api_resource(
+                        "/since/{bucket_name}",
+                        Method::GET,
+                        get_requests_since,
+                    ))
+                    .service(api_resource(
+                        "/dump/{bucket_name}",
+                        Method::GET,
+                        dump_bucket,
+                    ))
+                    .service(api_resource(
+                        "/import/{bucket_name}",
+                        Method::POST,
+                        import_bucket,
+                    ))
+                    .service(api_resource(
+                        "/expiry/{bucket_name}",
+                        Method::POST,
+                        set_password_expiry,
+                    ))
+                    .service(api_resource(
+                        "/rotate/{bucket_name}",
+                        Method::POST,
+                        rotate_bucket_password,
+                    ))
+                    .service(api_resource(
                         "/requests/{bucket_name}",
-                        web::get().to(get_bucket_requests),
-                    ),
-            )
-            .service(
-                web::scope("/ui").service(Files::new("/", "./static").index_file("index.html")),
+                        Method::GET,
+                        get_bucket_requests,
+                    )),
             )
+            .service(web::scope("/ui").service(ui_files))
             .route(
                 "/",
                 web::get().to(|| async {
@@ -66,12 +269,65 @@ async fn main() -> std::io::Result<()> {
                 }),
             )
             .default_service(web::route().to(capture_request))
-    })
-    .bind(&address)?
+    });
+    let server = match &listen_address {
+        ListenAddress::Tcp(address) => server.bind(address)?,
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            remove_stale_socket(path)?;
+            server.bind_uds(path)?
+        }
+        #[cfg(not(unix))]
+        ListenAddress::Unix(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "LISTEN_UDS is only supported on Unix platforms",
+            ))
+        }
+    }
     .run();
 
     let server_handle = server.handle();
 
+    // Restore buckets from the last snapshot and keep snapshotting them while PERSIST_PATH is set
+    let (restore_failed, mut restore_error) = tokio::sync::oneshot::channel();
+    if let Some(config) = &persist_config {
+        let app_state = snapshot_state.clone();
+        let server_handle = server_handle.clone();
+        let path = config.path.clone();
+        let mut interval = tokio::time::interval(config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        tokio::spawn(async move {
+            let restore_state = app_state.clone();
+            let restore_path = path.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                restore_snapshot(&restore_state, &restore_path)
+            })
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            match result {
+                Ok(restored) => {
+                    info!(path = %path.display(), restored, "Restored buckets from snapshot")
+                }
+                // Shut down still loading, so the unreadable snapshot is never overwritten
+                Err(e) => {
+                    error!(path = %path.display(), error = %e, "Failed to restore buckets from snapshot");
+                    let _ = restore_failed.send(e);
+                    server_handle.stop(true).await;
+                    return;
+                }
+            }
+            app_state.loading.store(false, Ordering::Release);
+
+            // The first tick completes immediately, and there is nothing new to save yet
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                snapshot(app_state.clone(), path.clone()).await;
+            }
+        });
+    }
+
     tokio::spawn(async move {
         tokio::signal::ctrl_c()
             .await
@@ -80,5 +336,42 @@ async fn main() -> std::io::Result<()> {
         server_handle.stop(true).await;
     });
 
-    server.await
+    server.await?;
+
+    // A snapshot that couldn't be restored fails the process, so supervisors don't see a clean exit
+    if let Ok(e) = restore_error.try_recv() {
+        return Err(std::io::Error::new(
+            e.kind(),
+            format!("Failed to restore buckets from snapshot: {}", e),
+        ));
+    }
+
+    // Skipped if the restore never completed, so the previous snapshot is kept
+    if let Some(config) = persist_config {
+        if !snapshot_state.loading.load(Ordering::Acquire) {
+            snapshot(snapshot_state, config.path).await;
+        }
+    }
+    Ok(())
+}
+
+// Remove a socket file left behind by a previous run, which would make binding fail
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+// Write a snapshot off the async runtime, logging failures (the previous snapshot stays intact)
+async fn snapshot(app_state: web::Data<AppState>, path: PathBuf) {
+    let result = tokio::task::spawn_blocking(move || write_snapshot(&app_state, &path)).await;
+    match result {
+        Ok(Ok(())) => info!("Wrote bucket snapshot"),
+        Ok(Err(e)) => error!(error = %e, "Failed to write bucket snapshot"),
+        Err(e) => error!(error = %e, "Bucket snapshot task failed"),
+    }
 }
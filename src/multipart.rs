@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+// Summary of a single multipart/form-data part
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MultipartPart {
+    pub name: Option<String>,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub size: usize,
+    // Decoded part content, only kept for parts at or below the inline size limit
+    pub content: Option<String>,
+}
+
+// Helper function to extract the boundary parameter from a multipart/form-data content type
+pub(crate) fn multipart_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+
+    params.find_map(|param| {
+        let (key, value) = param.trim().split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        let value = value.trim().trim_matches('"');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+// Split a multipart body into parts, keeping content inline for parts up to `inline_max_bytes`
+pub(crate) fn parse_multipart(
+    body: &[u8],
+    boundary: &str,
+    inline_max_bytes: usize,
+) -> Vec<MultipartPart> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let mut parts = Vec::new();
+
+    let mut position = match find_subsequence(body, delimiter, 0) {
+        Some(index) => index + delimiter.len(),
+        None => return parts,
+    };
+
+    loop {
+        // A delimiter followed by "--" closes the body
+        if body[position..].starts_with(b"--") {
+            break;
+        }
+        let start = skip_line_break(body, position);
+        let end = match find_subsequence(body, delimiter, start) {
+            Some(index) => index,
+            None => break,
+        };
+
+        // The line break before the next delimiter belongs to the delimiter
+        let raw_part = strip_trailing_line_break(&body[start..end]);
+        if let Some(part) = parse_part(raw_part, inline_max_bytes) {
+            parts.push(part);
+        }
+        position = end + delimiter.len();
+    }
+
+    parts
+}
+
+fn parse_part(raw_part: &[u8], inline_max_bytes: usize) -> Option<MultipartPart> {
+    let header_end = find_subsequence(raw_part, b"\r\n\r\n", 0)?;
+    let headers = String::from_utf8_lossy(&raw_part[..header_end]);
+    let content = &raw_part[header_end + 4..];
+
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+    for line in headers.split("\r\n") {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        if key.eq_ignore_ascii_case("content-disposition") {
+            name = disposition_param(value, "name");
+            filename = disposition_param(value, "filename");
+        } else if key.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.trim().to_string());
+        }
+    }
+
+    Some(MultipartPart {
+        name,
+        filename,
+        content_type,
+        size: content.len(),
+        content: (content.len() <= inline_max_bytes)
+            .then(|| String::from_utf8_lossy(content).into_owned()),
+    })
+}
+
+// Helper function to read a parameter such as `name="field"` from a Content-Disposition value
+fn disposition_param(value: &str, param: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|item| {
+        let (key, value) = item.trim().split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(param)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+fn find_subsequence(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from > haystack.len() {
+        return None;
+    }
+    haystack[from..]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|index| index + from)
+}
+
+fn skip_line_break(body: &[u8], position: usize) -> usize {
+    if body[position..].starts_with(b"\r\n") {
+        position + 2
+    } else if body[position..].starts_with(b"\n") {
+        position + 1
+    } else {
+        position
+    }
+}
+
+fn strip_trailing_line_break(part: &[u8]) -> &[u8] {
+    part.strip_suffix(b"\r\n")
+        .or_else(|| part.strip_suffix(b"\n"))
+        .unwrap_or(part)
+}
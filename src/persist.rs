@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering as AtomicOrdering;
+use std::time::Duration;
+
+use crate::{AppState, Bucket};
+
+const DEFAULT_SNAPSHOT_INTERVAL_SECONDS: u64 = 60;
+
+/// Where and how often buckets are snapshotted to disk, read from `PERSIST_PATH` and
+/// `SNAPSHOT_INTERVAL_SECONDS`.
+pub struct PersistConfig {
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+impl PersistConfig {
+    /// Read the configuration from the environment (None = persistence disabled).
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(path) = crate::parse_env_var::<PathBuf>("PERSIST_PATH")? else {
+            return Ok(None);
+        };
+        let seconds = crate::parse_env_var::<u64>("SNAPSHOT_INTERVAL_SECONDS")?
+            .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_SECONDS);
+        if seconds == 0 {
+            return Err("SNAPSHOT_INTERVAL_SECONDS must be greater than 0".to_string());
+        }
+        Ok(Some(PersistConfig {
+            path,
+            interval: Duration::from_secs(seconds),
+        }))
+    }
+}
+
+// Sibling file the snapshot is written to before being renamed over `path`
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    PathBuf::from(temp)
+}
+
+/// Write every bucket to `path` as JSON. The snapshot is written and synced to a temporary file
+/// first and renamed into place, so a crash or power loss never leaves a truncated snapshot behind.
+pub fn write_snapshot(app_state: &AppState, path: &Path) -> io::Result<()> {
+    // Copy the buckets out first so no bucket stays locked while serializing and writing
+    let buckets: BTreeMap<String, Bucket> = app_state
+        .buckets
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    let json = serde_json::to_vec(&buckets)?;
+
+    let temp = temp_path(path);
+    let mut file = File::create(&temp)?;
+    file.write_all(&json)?;
+    // The data must be on disk before the rename is, or the rename could survive without it
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp, path)?;
+    sync_parent_dir(path)
+}
+
+// Sync the directory holding `path`, so the rename itself survives a power loss
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+// Directories can't be opened for syncing on other platforms
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Load the buckets of a snapshot written by `write_snapshot` into the application state,
+/// returning how many were restored. A missing snapshot restores nothing.
+pub fn restore_snapshot(app_state: &AppState, path: &Path) -> io::Result<usize> {
+    let json = match fs::read(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let buckets: BTreeMap<String, Bucket> = serde_json::from_slice(&json)?;
+    let restored = buckets.len();
+    for (name, bucket) in buckets {
+        app_state
+            .total_bytes
+            .fetch_add(bucket.stored_bytes, AtomicOrdering::Relaxed);
+        if let Some(old) = app_state.buckets.insert(name, bucket) {
+            app_state
+                .total_bytes
+                .fetch_sub(old.stored_bytes, AtomicOrdering::Relaxed);
+        }
+    }
+    Ok(restored)
+}
@@ -0,0 +1,390 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::{Bucket, RequestData};
+
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, String>> + Send + 'a>>;
+
+/// Counter kept with a bucket's configuration, so every instance reports the same value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BucketCounter {
+    /// Captures not stored because of `capture_content_types`
+    Dropped,
+    /// Captures not stored because of `sample_rate`
+    SampledOut,
+    /// Captures not stored because of `ignore_user_agents`
+    Ignored,
+    /// Changes made to stored requests in place (e.g. tagging)
+    Revision,
+    /// Captures stored since the bucket was last cleared (for `fail_first_n`)
+    Captured,
+}
+
+/// Storage shared between instances, so buckets and captures are visible from every instance.
+///
+/// Each instance keeps serving from its in-memory buckets: handlers refresh a bucket from the
+/// store before using it and write their changes back, so the store is the source of truth.
+/// Implementations should copy what they need out of their arguments before returning the
+/// future, so callers don't hold a bucket lock while it runs.
+pub trait SharedStore: Send + Sync {
+    /// Load a bucket with its requests (None if it doesn't exist).
+    fn load_bucket(&self, name: &str) -> StoreFuture<'_, Option<Bucket>>;
+    /// Store a new bucket with its requests, replacing any existing bucket of that name.
+    fn create_bucket(&self, name: &str, bucket: &Bucket) -> StoreFuture<'_, ()>;
+    /// Update a bucket's password, configuration and paused state, keeping its requests.
+    fn update_bucket(&self, name: &str, bucket: &Bucket) -> StoreFuture<'_, ()>;
+    /// Append a captured request, keeping only the newest `keep` requests (the ones left in
+    /// memory after the bucket's count and byte limits were applied).
+    fn push_request(&self, name: &str, request: &RequestData, keep: usize) -> StoreFuture<'_, ()>;
+    /// Replace the stored request with the same `seq` (e.g. after tagging it).
+    fn update_request(&self, name: &str, request: &RequestData) -> StoreFuture<'_, ()>;
+    /// Remove the requests with the given `seq`s (e.g. after they expired). Also called with no
+    /// `seq`s, which changes nothing but should still reach the store, to keep timing uniform.
+    fn remove_requests(&self, name: &str, seqs: &[u64]) -> StoreFuture<'_, ()>;
+    /// Add one to a bucket counter.
+    fn increment_counter(&self, name: &str, counter: BucketCounter) -> StoreFuture<'_, ()>;
+    /// Remove every request of a bucket and reset the counters clearing resets, invalidating
+    /// outstanding watermarks.
+    fn clear_requests(&self, name: &str) -> StoreFuture<'_, ()>;
+    fn delete_bucket(&self, name: &str) -> StoreFuture<'_, ()>;
+}
+
+// Build the shared store from the `REDIS_URL` environment variable (None = in-memory only)
+#[cfg(feature = "redis")]
+pub(crate) fn store_from_env() -> Result<Option<Box<dyn SharedStore>>, String> {
+    crate::parse_env_var::<String>("REDIS_URL")?
+        .map(|url| redis_store(&url))
+        .transpose()
+}
+
+#[cfg(not(feature = "redis"))]
+pub(crate) fn store_from_env() -> Result<Option<Box<dyn SharedStore>>, String> {
+    if std::env::var_os("REDIS_URL").is_some() {
+        tracing::warn!("REDIS_URL is set but the redis feature is not enabled; ignoring");
+    }
+    Ok(None)
+}
+
+/// Create a Redis-backed shared store for the given `redis://` URL.
+#[cfg(feature = "redis")]
+pub fn redis_store(url: &str) -> Result<Box<dyn SharedStore>, String> {
+    Ok(Box::new(redis_backend::RedisStore::new(url)?))
+}
+
+#[cfg(feature = "redis")]
+mod redis_backend {
+    use std::collections::HashMap;
+
+    use redis::aio::MultiplexedConnection;
+    use redis::AsyncCommands;
+    use tokio::sync::OnceCell;
+
+    use super::{BucketCounter, SharedStore, StoreFuture};
+    use crate::{Bucket, BucketConfig, RequestData};
+
+    // Password, configuration and counters of a bucket are kept in a hash, its requests
+    // (oldest first) in a list trimmed to what the bucket's limits allow
+    fn meta_key(name: &str) -> String {
+        format!("request-catcher:bucket:{}", name)
+    }
+
+    fn requests_key(name: &str) -> String {
+        format!("request-catcher:requests:{}", name)
+    }
+
+    // Hash field holding a bucket counter
+    fn counter_field(counter: BucketCounter) -> &'static str {
+        match counter {
+            BucketCounter::Dropped => "dropped_count",
+            BucketCounter::SampledOut => "sampled_out_count",
+            BucketCounter::Ignored => "ignored_count",
+            BucketCounter::Revision => "revision",
+            BucketCounter::Captured => "capture_count",
+        }
+    }
+
+    const COUNTERS: [BucketCounter; 5] = [
+        BucketCounter::Dropped,
+        BucketCounter::SampledOut,
+        BucketCounter::Ignored,
+        BucketCounter::Revision,
+        BucketCounter::Captured,
+    ];
+
+    // Counters `clear_requests` resets, as clearing a bucket does
+    const CLEARED_COUNTERS: [BucketCounter; 4] = [
+        BucketCounter::Dropped,
+        BucketCounter::SampledOut,
+        BucketCounter::Ignored,
+        BucketCounter::Captured,
+    ];
+
+    // Replaces the request whose `seq` is ARGV[1] in the list at KEYS[1] with ARGV[2], as
+    // positions can differ between instances
+    const UPDATE_REQUEST_SCRIPT: &str = r#"
+        local seq = tonumber(ARGV[1])
+        local requests = redis.call('LRANGE', KEYS[1], 0, -1)
+        for index, request in ipairs(requests) do
+            if cjson.decode(request).seq == seq then
+                redis.call('LSET', KEYS[1], index - 1, ARGV[2])
+                return
+            end
+        end
+    "#;
+
+    // Removes the requests whose `seq` is one of ARGV from the list at KEYS[1]
+    const REMOVE_REQUESTS_SCRIPT: &str = r#"
+        if #ARGV == 0 then
+            return
+        end
+        local remove = {}
+        for _, seq in ipairs(ARGV) do
+            remove[tonumber(seq)] = true
+        end
+        local requests = redis.call('LRANGE', KEYS[1], 0, -1)
+        for index, request in ipairs(requests) do
+            if remove[cjson.decode(request).seq] then
+                redis.call('LSET', KEYS[1], index - 1, '')
+            end
+        end
+        redis.call('LREM', KEYS[1], 0, '')
+    "#;
+
+    // Hash fields describing a bucket, apart from its counters
+    fn bucket_fields(bucket: &Bucket) -> Result<Vec<(&'static str, String)>, String> {
+        let to_json = |e: serde_json::Error| e.to_string();
+        Ok(vec![
+            ("password", bucket.password.clone()),
+            (
+                "config",
+                serde_json::to_string(&bucket.config).map_err(to_json)?,
+            ),
+            (
+                "password_expires_at",
+                serde_json::to_string(&bucket.password_expires_at).map_err(to_json)?,
+            ),
+            ("enabled", bucket.enabled.to_string()),
+        ])
+    }
+
+    pub(super) struct RedisStore {
+        client: redis::Client,
+        // Opened on first use and shared by every operation (clones multiplex over it)
+        connection: OnceCell<MultiplexedConnection>,
+    }
+
+    impl RedisStore {
+        pub(super) fn new(url: &str) -> Result<Self, String> {
+            let client = redis::Client::open(url)
+                .map_err(|e| format!("Invalid value for REDIS_URL: {}", e))?;
+            Ok(RedisStore {
+                client,
+                connection: OnceCell::new(),
+            })
+        }
+
+        async fn connection(&self) -> Result<MultiplexedConnection, String> {
+            self.connection
+                .get_or_try_init(|| async {
+                    self.client
+                        .get_multiplexed_async_connection()
+                        .await
+                        .map_err(|e| format!("Failed to connect to Redis: {}", e))
+                })
+                .await
+                .cloned()
+        }
+    }
+
+    fn redis_error(e: redis::RedisError) -> String {
+        format!("Redis command failed: {}", e)
+    }
+
+    impl SharedStore for RedisStore {
+        fn load_bucket(&self, name: &str) -> StoreFuture<'_, Option<Bucket>> {
+            let (meta_key, requests_key) = (meta_key(name), requests_key(name));
+            Box::pin(async move {
+                let mut con = self.connection().await?;
+                // One round trip whether or not the bucket exists, so its timing doesn't tell
+                let (meta, requests): (HashMap<String, String>, Vec<String>) = redis::pipe()
+                    .atomic()
+                    .hgetall(&meta_key)
+                    .lrange(&requests_key, 0, -1)
+                    .query_async(&mut con)
+                    .await
+                    .map_err(redis_error)?;
+                // Captures racing a delete can leave a hash with only counters behind
+                let Some(password) = meta.get("password") else {
+                    return Ok(None);
+                };
+
+                let parse = |field: &str| meta.get(field).map(String::as_str).unwrap_or("null");
+                let config: Option<BucketConfig> =
+                    serde_json::from_str(parse("config")).map_err(|e| e.to_string())?;
+                let mut bucket = Bucket::new(password.clone(), config.unwrap_or_default());
+                bucket.password_expires_at = serde_json::from_str(parse("password_expires_at"))
+                    .map_err(|e| e.to_string())?;
+                // Loaded rather than pushed, so requests keep their `seq`; the byte quota still
+                // applies, as the list is only capped by count
+                for request in requests {
+                    let request: RequestData =
+                        serde_json::from_str(&request).map_err(|e| e.to_string())?;
+                    bucket.push_loaded(request);
+                }
+                bucket.enabled = parse("enabled").parse().unwrap_or(true);
+                bucket.last_id = parse("last_id").parse().unwrap_or(bucket.last_id);
+                bucket.generation = parse("generation").parse().unwrap_or_default();
+                for counter in COUNTERS {
+                    *bucket.counter_mut(counter) =
+                        parse(counter_field(counter)).parse().unwrap_or_default();
+                }
+                Ok(Some(bucket))
+            })
+        }
+
+        fn create_bucket(&self, name: &str, bucket: &Bucket) -> StoreFuture<'_, ()> {
+            let (meta_key, requests_key) = (meta_key(name), requests_key(name));
+            let fields = bucket_fields(bucket).map(|mut fields| {
+                fields.push(("last_id", bucket.last_id.to_string()));
+                fields.push(("generation", bucket.generation.to_string()));
+                for counter in COUNTERS {
+                    fields.push((counter_field(counter), bucket.counter(counter).to_string()));
+                }
+                fields
+            });
+            let requests: Result<Vec<String>, String> = bucket
+                .requests
+                .iter()
+                .map(|request| serde_json::to_string(request).map_err(|e| e.to_string()))
+                .collect();
+            Box::pin(async move {
+                let (fields, requests) = (fields?, requests?);
+                let mut con = self.connection().await?;
+                let mut pipe = redis::pipe();
+                pipe.atomic()
+                    .del(&[&meta_key, &requests_key])
+                    .ignore()
+                    .hset_multiple(&meta_key, &fields)
+                    .ignore();
+                if !requests.is_empty() {
+                    pipe.rpush(&requests_key, requests).ignore();
+                }
+                pipe.query_async::<()>(&mut con).await.map_err(redis_error)
+            })
+        }
+
+        fn update_bucket(&self, name: &str, bucket: &Bucket) -> StoreFuture<'_, ()> {
+            let meta_key = meta_key(name);
+            let fields = bucket_fields(bucket);
+            Box::pin(async move {
+                let fields = fields?;
+                let mut con = self.connection().await?;
+                con.hset_multiple::<_, _, _, ()>(&meta_key, &fields)
+                    .await
+                    .map_err(redis_error)
+            })
+        }
+
+        fn push_request(
+            &self,
+            name: &str,
+            request: &RequestData,
+            keep: usize,
+        ) -> StoreFuture<'_, ()> {
+            let (meta_key, requests_key) = (meta_key(name), requests_key(name));
+            let request = serde_json::to_string(request).map_err(|e| e.to_string());
+            Box::pin(async move {
+                let request = request?;
+                let mut con = self.connection().await?;
+                redis::pipe()
+                    .atomic()
+                    .rpush(&requests_key, request)
+                    .ignore()
+                    .ltrim(&requests_key, -(keep as isize), -1)
+                    .ignore()
+                    .hincr(&meta_key, "last_id", 1)
+                    .ignore()
+                    .query_async::<()>(&mut con)
+                    .await
+                    .map_err(redis_error)
+            })
+        }
+
+        fn update_request(&self, name: &str, request: &RequestData) -> StoreFuture<'_, ()> {
+            let requests_key = requests_key(name);
+            let seq = request.seq;
+            let request = serde_json::to_string(request).map_err(|e| e.to_string());
+            Box::pin(async move {
+                let request = request?;
+                let mut con = self.connection().await?;
+                redis::cmd("EVAL")
+                    .arg(UPDATE_REQUEST_SCRIPT)
+                    .arg(1)
+                    .arg(&requests_key)
+                    .arg(seq)
+                    .arg(request)
+                    .query_async::<()>(&mut con)
+                    .await
+                    .map_err(redis_error)
+            })
+        }
+
+        fn remove_requests(&self, name: &str, seqs: &[u64]) -> StoreFuture<'_, ()> {
+            let requests_key = requests_key(name);
+            let seqs = seqs.to_vec();
+            Box::pin(async move {
+                let mut con = self.connection().await?;
+                redis::cmd("EVAL")
+                    .arg(REMOVE_REQUESTS_SCRIPT)
+                    .arg(1)
+                    .arg(&requests_key)
+                    .arg(seqs)
+                    .query_async::<()>(&mut con)
+                    .await
+                    .map_err(redis_error)
+            })
+        }
+
+        fn increment_counter(&self, name: &str, counter: BucketCounter) -> StoreFuture<'_, ()> {
+            let meta_key = meta_key(name);
+            Box::pin(async move {
+                let mut con = self.connection().await?;
+                con.hincr::<_, _, _, ()>(&meta_key, counter_field(counter), 1)
+                    .await
+                    .map_err(redis_error)
+            })
+        }
+
+        fn clear_requests(&self, name: &str) -> StoreFuture<'_, ()> {
+            let (meta_key, requests_key) = (meta_key(name), requests_key(name));
+            let reset: Vec<(&str, u64)> = CLEARED_COUNTERS
+                .iter()
+                .map(|&counter| (counter_field(counter), 0))
+                .collect();
+            Box::pin(async move {
+                let mut con = self.connection().await?;
+                redis::pipe()
+                    .atomic()
+                    .del(&requests_key)
+                    .ignore()
+                    .hincr(&meta_key, "generation", 1)
+                    .ignore()
+                    .hset_multiple(&meta_key, &reset)
+                    .ignore()
+                    .query_async::<()>(&mut con)
+                    .await
+                    .map_err(redis_error)
+            })
+        }
+
+        fn delete_bucket(&self, name: &str) -> StoreFuture<'_, ()> {
+            let (meta_key, requests_key) = (meta_key(name), requests_key(name));
+            Box::pin(async move {
+                let mut con = self.connection().await?;
+                con.del::<_, ()>(&[&meta_key, &requests_key])
+                    .await
+                    .map_err(redis_error)
+            })
+        }
+    }
+}
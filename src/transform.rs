@@ -0,0 +1,122 @@
+use jaq_core::load::{self, Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, Filter, Native, RcIter};
+use jaq_json::Val;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Most values a jq program may output for one body
+const MAX_JQ_OUTPUTS: usize = 1000;
+// Largest compact JSON a jq program may output for one body
+const MAX_JQ_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// A bucket's jq program, compiled once when the bucket's configuration is read. Serialized as
+/// the program text.
+#[derive(Clone)]
+pub struct JqFilter {
+    program: String,
+    filter: Arc<Filter<Native<Val>>>,
+}
+
+impl JqFilter {
+    pub fn new(program: &str) -> Result<Self, String> {
+        let filter = compile(program).map_err(|e| format!("Invalid jq_filter: {}", e))?;
+        Ok(JqFilter {
+            program: program.to_string(),
+            filter: Arc::new(filter),
+        })
+    }
+}
+
+impl Serialize for JqFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.program)
+    }
+}
+
+impl<'de> Deserialize<'de> for JqFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let program = String::deserialize(deserializer)?;
+        JqFilter::new(&program).map_err(serde::de::Error::custom)
+    }
+}
+
+// Helper function to compile a jq program against the jq standard library, describing the first
+// problem found when it is invalid
+fn compile(program: &str) -> Result<Filter<Native<Val>>, String> {
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let arena = Arena::default();
+    let file = File {
+        code: program,
+        path: (),
+    };
+    let modules = loader.load(&arena, file).map_err(|errors| {
+        let message = errors.into_iter().find_map(|(_, error)| match error {
+            load::Error::Io(errors) => errors.into_iter().next().map(|(_, e)| e),
+            load::Error::Lex(errors) => errors
+                .first()
+                .map(|(expected, _)| format!("expected {}", expected.as_str())),
+            load::Error::Parse(errors) => errors
+                .first()
+                .map(|(expected, _)| format!("expected {}", expected.as_str())),
+        });
+        message.unwrap_or_else(|| "syntax error".to_string())
+    })?;
+
+    Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+        .map_err(|errors| {
+            errors
+                .into_iter()
+                .flat_map(|(_, errors)| errors)
+                .next()
+                .map(|(name, undefined)| format!("undefined {} {}", undefined.as_str(), name))
+                .unwrap_or_else(|| "compile error".to_string())
+        })
+}
+
+// Run a jq program over a JSON value, returning the compact JSON of its output (an array when it
+// produces several values). Err when the program fails on it or its output is over
+// MAX_JQ_OUTPUTS values or MAX_JQ_OUTPUT_BYTES.
+fn apply_jq_filter(
+    filter: &Filter<Native<Val>>,
+    input: serde_json::Value,
+) -> Result<String, String> {
+    let inputs = RcIter::new(core::iter::empty());
+    let mut outputs = Vec::new();
+    let mut output_bytes = 0;
+    for output in filter.run((Ctx::new([], &inputs), Val::from(input))) {
+        let output = serde_json::Value::from(output.map_err(|e| e.to_string())?).to_string();
+        output_bytes += output.len() + 1;
+        if outputs.len() == MAX_JQ_OUTPUTS {
+            return Err(format!("more than {} outputs", MAX_JQ_OUTPUTS));
+        }
+        if output_bytes > MAX_JQ_OUTPUT_BYTES {
+            return Err(format!("output over {} bytes", MAX_JQ_OUTPUT_BYTES));
+        }
+        outputs.push(output);
+    }
+    if outputs.len() == 1 {
+        Ok(outputs.remove(0))
+    } else {
+        Ok(format!("[{}]", outputs.join(",")))
+    }
+}
+
+// Run a bucket's jq program over a JSON body on the blocking thread pool, giving up on it after
+// `timeout` (see `apply_jq_filter`). A program still running then keeps its thread until it
+// finishes
+pub(crate) async fn run_jq_filter(
+    filter: &JqFilter,
+    input: serde_json::Value,
+    timeout: Duration,
+) -> Result<String, String> {
+    let compiled = Arc::clone(&filter.filter);
+    let task = tokio::task::spawn_blocking(move || apply_jq_filter(&compiled, input));
+    match tokio::time::timeout(timeout, task).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(format!("jq_filter stopped unexpectedly: {}", e)),
+        Err(_) => Err(format!("timed out after {:?}", timeout)),
+    }
+}